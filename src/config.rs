@@ -0,0 +1,289 @@
+//! The configuration file, written in a small subset of TOML.
+//!
+//! Top-level keys are long option names and set defaults, `[profile.<name>]`
+//! tables override them when picked with `--profile <name>`:
+//!
+//! ```toml
+//! colour = "#ff0000"
+//! select-thickness = 2
+//!
+//! [profile.record]
+//! format = "%wx%h %x,%y"
+//! dim = true
+//! ```
+//!
+//! Only bare keys, strings, integers and booleans are supported.
+
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Value {
+    Boolean(bool),
+    Integer(i64),
+    String(String),
+}
+
+type Table = Vec<(String, Value)>;
+
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Config {
+    defaults: Table,
+    profiles: Vec<(String, Table)>,
+}
+
+impl Config {
+    /// Command line arguments for the defaults, overridden by `profile`,
+    /// leaving out the keys for which `skip` is true.
+    pub(crate) fn args(
+        &self,
+        profile: Option<&str>,
+        skip: impl Fn(&str) -> bool,
+    ) -> Result<Vec<OsString>, String> {
+        let mut entries = self
+            .defaults
+            .iter()
+            .map(|(key, value)| (key, value))
+            .collect::<Vec<_>>();
+
+        if let Some(profile) = profile {
+            let (_, table) = self
+                .profiles
+                .iter()
+                .find(|(name, _)| name == profile)
+                .ok_or_else(|| format!("No profile named \"{}\"", profile))?;
+            for (key, value) in table {
+                entries.retain(|(other, _)| *other != key);
+                entries.push((key, value));
+            }
+        }
+
+        Ok(entries
+            .into_iter()
+            .filter(|(key, _)| !skip(key))
+            .filter_map(|(key, value)| match value {
+                Value::Boolean(true) => Some(format!("--{}", key)),
+                Value::Boolean(false) => None,
+                Value::Integer(n) => Some(format!("--{}={}", key, n)),
+                Value::String(s) => Some(format!("--{}={}", key, s)),
+            })
+            .map(OsString::from)
+            .collect())
+    }
+}
+
+/// `$XDG_CONFIG_HOME/hacksaw/config.toml`, with `~/.config` if that is unset.
+fn path() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("hacksaw").join("config.toml"))
+}
+
+/// Read the configuration file. A missing one is the same as an empty one.
+pub(crate) fn load() -> Result<Config, String> {
+    let path = match path() {
+        Some(path) => path,
+        None => return Ok(Config::default()),
+    };
+
+    match fs::read_to_string(&path) {
+        Ok(input) => parse(&input),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(err) => Err(err.to_string()),
+    }
+    .map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+pub(crate) fn parse(input: &str) -> Result<Config, String> {
+    let mut config = Config::default();
+    for (number, line) in input.lines().enumerate() {
+        parse_line(&mut config, line).map_err(|err| format!("line {}: {}", number + 1, err))?;
+    }
+    Ok(config)
+}
+
+fn parse_line(config: &mut Config, line: &str) -> Result<(), String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(());
+    }
+
+    if let Some(header) = line.strip_prefix('[') {
+        let (header, rest) = header.split_once(']').ok_or("Unterminated table header")?;
+        expect_end(rest)?;
+        let name = header
+            .trim()
+            .strip_prefix("profile.")
+            .filter(|name| is_bare_key(name))
+            .ok_or_else(|| format!("Expected [profile.<name>], found [{}]", header))?;
+        if config.profiles.iter().any(|(other, _)| other == name) {
+            return Err(format!("Profile \"{}\" defined twice", name));
+        }
+        config.profiles.push((name.to_owned(), Vec::new()));
+        return Ok(());
+    }
+
+    let (key, value) = line.split_once('=').ok_or("Expected key = value")?;
+    let key = key.trim();
+    if !is_bare_key(key) {
+        return Err(format!("Invalid key \"{}\"", key));
+    }
+    let (value, rest) = parse_value(value.trim_start())?;
+    expect_end(rest)?;
+
+    // Keys belong to the last table header
+    let table = match config.profiles.last_mut() {
+        Some((_, table)) => table,
+        None => &mut config.defaults,
+    };
+    let key = key.replace('_', "-");
+    if table.iter().any(|(other, _)| *other == key) {
+        return Err(format!("Key \"{}\" defined twice", key));
+    }
+    table.push((key, value));
+    Ok(())
+}
+
+fn is_bare_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Allow nothing but a comment after a value or table header.
+fn expect_end(rest: &str) -> Result<(), String> {
+    let rest = rest.trim_start();
+    if rest.is_empty() || rest.starts_with('#') {
+        Ok(())
+    } else {
+        Err(format!("Unexpected \"{}\"", rest))
+    }
+}
+
+fn parse_value(input: &str) -> Result<(Value, &str), String> {
+    if let Some(rest) = input.strip_prefix('"') {
+        return parse_basic_string(rest);
+    }
+    if let Some(rest) = input.strip_prefix('\'') {
+        let (literal, rest) = rest.split_once('\'').ok_or("Unterminated string")?;
+        return Ok((Value::String(literal.to_owned()), rest));
+    }
+
+    let end = input
+        .find(|c: char| c.is_whitespace() || c == '#')
+        .unwrap_or(input.len());
+    let (word, rest) = input.split_at(end);
+    let value = match word {
+        "" => return Err("Expected a value".to_owned()),
+        "true" => Value::Boolean(true),
+        "false" => Value::Boolean(false),
+        _ => word
+            .replace('_', "")
+            .parse()
+            .map(Value::Integer)
+            .map_err(|_| format!("Unsupported value \"{}\"", word))?,
+    };
+    Ok((value, rest))
+}
+
+/// Parse the rest of a `"`-quoted string, after the opening quote.
+fn parse_basic_string(input: &str) -> Result<(Value, &str), String> {
+    let mut value = String::new();
+    let mut chars = input.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((Value::String(value), &input[i + 1..])),
+            '\\' => {
+                let escaped = match chars.next().map(|(_, c)| c) {
+                    Some('"') => '"',
+                    Some('\\') => '\\',
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some(c @ ('u' | 'U')) => {
+                        let len = if c == 'u' { 4 } else { 8 };
+                        let hex = chars.by_ref().take(len).map(|(_, c)| c).collect::<String>();
+                        u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| format!("Invalid escape \"\\{}{}\"", c, hex))?
+                    }
+                    Some(c) => return Err(format!("Invalid escape \"\\{}\"", c)),
+                    None => break,
+                };
+                value.push(escaped);
+            }
+            c => value.push(c),
+        }
+    }
+
+    Err("Unterminated string".to_owned())
+}
+
+#[test]
+fn test_parse() {
+    let config = parse(
+        r##"
+# Shared defaults
+colour = "#ff0000"  # red
+select_thickness = 2
+dim = true
+
+[profile.record]
+format = '%wx%h %x,%y'
+dim = false
+remove-decorations = 1_0
+
+[ profile.quote ]
+format = "\"%g\"\t\u00e9"
+"##,
+    )
+    .unwrap();
+
+    let args = |profile| {
+        config
+            .args(profile, |_| false)
+            .unwrap()
+            .into_iter()
+            .map(|arg| arg.into_string().unwrap())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        args(None),
+        ["--colour=#ff0000", "--select-thickness=2", "--dim"]
+    );
+    assert_eq!(
+        config.args(None, |key| key == "dim").unwrap(),
+        ["--colour=#ff0000", "--select-thickness=2"]
+    );
+    assert_eq!(
+        args(Some("record")),
+        [
+            "--colour=#ff0000",
+            "--select-thickness=2",
+            "--format=%wx%h %x,%y",
+            "--remove-decorations=10",
+        ]
+    );
+    assert_eq!(args(Some("quote"))[3], "--format=\"%g\"\t\u{e9}".to_owned());
+    assert!(config.args(Some("missing"), |_| false).is_err());
+
+    assert_eq!(parse(""), Ok(Config::default()));
+    assert_eq!(
+        parse("a = 1\n\nb = yes"),
+        Err("line 3: Unsupported value \"yes\"".into())
+    );
+    assert!(parse("a = 1\na = 2").is_err());
+    assert!(parse("a = \"open").is_err());
+    assert!(parse("a = 1 2").is_err());
+    assert!(parse("a.b = 1").is_err());
+    assert!(parse("[colours]").is_err());
+    assert!(parse("[profile.a]\n[profile.a]").is_err());
+}
@@ -0,0 +1,143 @@
+use super::selection::Cancel;
+use super::CURSOR_GRAB_TRIES;
+
+use std::{error, fmt, io};
+
+/// X error codes for requests on a window that does not exist (anymore).
+/// Requests that work on any drawable, like `GetGeometry`, fail with the
+/// latter.
+const BAD_WINDOW: u8 = 3;
+const BAD_DRAWABLE: u8 = 9;
+
+/// Everything that can stop hacksaw from producing a selection.
+///
+/// Each variant maps to its own process exit code (see
+/// [`HacksawError::exit_code`]), which scripts can rely on.
+#[derive(Debug)]
+pub enum HacksawError {
+    /// The user cancelled with the Escape key.
+    Escape,
+    /// The user cancelled with a right click.
+    RightClick,
+    /// Could not connect to the X server, e.g. because `DISPLAY` is unset.
+    Connection(xcb::ConnError),
+    /// The connection to the X server broke while waiting for events.
+    ConnectionLost,
+    /// Another client kept the pointer grabbed.
+    GrabFailed,
+    /// No key on the keyboard produces Escape, so there would be no way out.
+    NoEscapeKey,
+    /// A window disappeared while it was being queried.
+    WindowVanished,
+    /// Any other X request failed, with its X error code.
+    Request(u8),
+    /// The pixel format of the screen is not one we can take screenshots of.
+    UnsupportedVisual,
+    /// Writing the screenshot failed.
+    Io(io::Error),
+    /// The region to take a screenshot of has no pixels on the screen.
+    EmptyCapture,
+}
+
+impl HacksawError {
+    /// Exit code for this error. 1 is left to argument parsing errors.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            HacksawError::Escape => 2,
+            HacksawError::RightClick => 3,
+            HacksawError::Connection(_) => 4,
+            HacksawError::ConnectionLost => 5,
+            HacksawError::GrabFailed => 6,
+            HacksawError::NoEscapeKey => 7,
+            HacksawError::WindowVanished => 8,
+            HacksawError::Request(_) => 9,
+            HacksawError::UnsupportedVisual => 10,
+            HacksawError::Io(_) => 11,
+            HacksawError::EmptyCapture => 12,
+        }
+    }
+}
+
+impl fmt::Display for HacksawError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HacksawError::Escape => write!(f, "Exiting due to ESC key press"),
+            HacksawError::RightClick => write!(f, "Exiting due to right click"),
+            HacksawError::Connection(err) => write!(f, "Could not connect to X server: {}", err),
+            HacksawError::ConnectionLost => write!(f, "Error getting X event, quitting."),
+            HacksawError::GrabFailed => write!(
+                f,
+                "Failed to grab cursor after {} tries, giving up",
+                CURSOR_GRAB_TRIES
+            ),
+            HacksawError::NoEscapeKey => write!(f, "Failed to find escape keysym"),
+            HacksawError::WindowVanished => write!(f, "Window vanished while querying it"),
+            HacksawError::Request(code) => write!(f, "X request failed with error code {}", code),
+            HacksawError::UnsupportedVisual => write!(f, "Unsupported screen pixel format"),
+            HacksawError::Io(err) => write!(f, "Could not write image: {}", err),
+            HacksawError::EmptyCapture => write!(f, "Nothing to capture in an empty region"),
+        }
+    }
+}
+
+impl error::Error for HacksawError {}
+
+impl From<xcb::ConnError> for HacksawError {
+    fn from(err: xcb::ConnError) -> HacksawError {
+        HacksawError::Connection(err)
+    }
+}
+
+impl HacksawError {
+    /// The error for a failed X request with this error code.
+    fn from_error_code(code: u8) -> HacksawError {
+        match code {
+            BAD_WINDOW | BAD_DRAWABLE => HacksawError::WindowVanished,
+            code => HacksawError::Request(code),
+        }
+    }
+}
+
+impl From<xcb::GenericError> for HacksawError {
+    fn from(err: xcb::GenericError) -> HacksawError {
+        HacksawError::from_error_code(err.error_code())
+    }
+}
+
+impl From<io::Error> for HacksawError {
+    fn from(err: io::Error) -> HacksawError {
+        HacksawError::Io(err)
+    }
+}
+
+impl From<Cancel> for HacksawError {
+    fn from(cancel: Cancel) -> HacksawError {
+        match cancel {
+            Cancel::Escape => HacksawError::Escape,
+            Cancel::RightClick => HacksawError::RightClick,
+        }
+    }
+}
+
+#[test]
+fn test_exit_codes() {
+    // Scripts depend on these, don't renumber them
+    assert_eq!(HacksawError::from(Cancel::Escape).exit_code(), 2);
+    assert_eq!(HacksawError::from(Cancel::RightClick).exit_code(), 3);
+    assert_eq!(
+        HacksawError::from(xcb::ConnError::ClosedParseErr).exit_code(),
+        4
+    );
+    assert_eq!(HacksawError::ConnectionLost.exit_code(), 5);
+    assert_eq!(HacksawError::GrabFailed.exit_code(), 6);
+    assert_eq!(HacksawError::NoEscapeKey.exit_code(), 7);
+    assert_eq!(HacksawError::from_error_code(BAD_WINDOW).exit_code(), 8);
+    assert_eq!(HacksawError::from_error_code(BAD_DRAWABLE).exit_code(), 8);
+    assert_eq!(HacksawError::from_error_code(8).exit_code(), 9);
+    assert_eq!(HacksawError::UnsupportedVisual.exit_code(), 10);
+    assert_eq!(
+        HacksawError::from(io::Error::from(io::ErrorKind::NotFound)).exit_code(),
+        11
+    );
+    assert_eq!(HacksawError::EmptyCapture.exit_code(), 12);
+}
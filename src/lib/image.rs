@@ -0,0 +1,225 @@
+//! Decoding of Z-pixmap images as returned by `GetImage`, and screenshots.
+
+use super::png;
+use super::HacksawError;
+
+use std::io::{self, Write};
+
+/// Layout of the pixels of images in a given depth and visual.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PixelFormat {
+    pub depth: u8,
+    pub bits_per_pixel: u8,
+    pub scanline_pad: u8,
+    pub lsb_first: bool,
+    pub red_mask: u32,
+    pub green_mask: u32,
+    pub blue_mask: u32,
+}
+
+impl PixelFormat {
+    /// The format of images of the root window of `screen`.
+    pub fn of_root(setup: &xcb::Setup, screen: &xcb::Screen) -> Option<PixelFormat> {
+        let depth = screen.root_depth();
+        let format = setup
+            .pixmap_formats()
+            .find(|format| format.depth() == depth)?;
+        let visual = screen
+            .allowed_depths()
+            .flat_map(|depth| depth.visuals())
+            .find(|visual| visual.visual_id() == screen.root_visual())?;
+
+        Some(PixelFormat {
+            depth,
+            bits_per_pixel: format.bits_per_pixel(),
+            scanline_pad: format.scanline_pad(),
+            lsb_first: setup.image_byte_order() == xcb::IMAGE_ORDER_LSB_FIRST as u8,
+            red_mask: visual.red_mask(),
+            green_mask: visual.green_mask(),
+            blue_mask: visual.blue_mask(),
+        })
+    }
+
+    pub fn bytes_per_pixel(&self) -> usize {
+        (self.bits_per_pixel as usize).div_ceil(8)
+    }
+
+    /// Number of bytes per row of an image `width` pixels wide.
+    pub fn stride(&self, width: u16) -> usize {
+        let pad = self.scanline_pad.max(8) as usize;
+        (width as usize * self.bits_per_pixel as usize).div_ceil(pad) * pad / 8
+    }
+
+    /// Read the pixel value at `x`, `y` of an image `width` pixels wide.
+    pub fn pixel(&self, data: &[u8], width: u16, x: u16, y: u16) -> u32 {
+        let offset = y as usize * self.stride(width) + x as usize * self.bytes_per_pixel();
+        let bytes = &data[offset..offset + self.bytes_per_pixel()];
+        if self.lsb_first {
+            bytes
+                .iter()
+                .rev()
+                .fold(0, |pixel, &byte| pixel << 8 | u32::from(byte))
+        } else {
+            bytes
+                .iter()
+                .fold(0, |pixel, &byte| pixel << 8 | u32::from(byte))
+        }
+    }
+
+    /// Write `pixel` at `x`, `y` of an image `width` pixels wide.
+    pub fn put_pixel(&self, data: &mut [u8], width: u16, x: u16, y: u16, pixel: u32) {
+        let offset = y as usize * self.stride(width) + x as usize * self.bytes_per_pixel();
+        let bytes = &mut data[offset..offset + self.bytes_per_pixel()];
+        let len = bytes.len();
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            let shift = if self.lsb_first { i } else { len - 1 - i };
+            *byte = (pixel >> (8 * shift)) as u8;
+        }
+    }
+
+    /// Convert a pixel value to 8-bit RGB.
+    pub fn rgb(&self, pixel: u32) -> [u8; 3] {
+        [
+            channel(pixel, self.red_mask),
+            channel(pixel, self.green_mask),
+            channel(pixel, self.blue_mask),
+        ]
+    }
+}
+
+/// An 8-bit RGB image.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width: u16,
+    pub height: u16,
+    /// Packed RGB triplets, row by row.
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    /// Convert a Z-pixmap image in `format` to RGB.
+    pub fn from_z_pixmap(format: &PixelFormat, data: &[u8], width: u16, height: u16) -> Image {
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 3);
+        for y in 0..height {
+            for x in 0..width {
+                pixels.extend_from_slice(&format.rgb(format.pixel(data, width, x, y)));
+            }
+        }
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn write_png(&self, out: &mut dyn Write) -> io::Result<()> {
+        png::write(out, self.width, self.height, &self.pixels)
+    }
+
+    /// Write the image as a binary PPM (P6) file.
+    pub fn write_ppm(&self, out: &mut dyn Write) -> io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+        out.write_all(&self.pixels)
+    }
+}
+
+/// Take a screenshot of `rect` on the default display. Parts of `rect`
+/// outside of the screen are cut off, and nothing being left is an error.
+///
+/// Any of our own windows should be gone by now, which [`select`] takes care
+/// of before returning.
+///
+/// [`select`]: super::select
+pub fn capture(rect: xcb::Rectangle) -> Result<Image, HacksawError> {
+    let (conn, screen_num) = xcb::Connection::connect(None)?;
+    let setup = conn.get_setup();
+    let screen = setup
+        .roots()
+        .nth(screen_num as usize)
+        .ok_or(xcb::ConnError::ClosedInvalidScreen)?;
+    let format = PixelFormat::of_root(&setup, &screen).ok_or(HacksawError::UnsupportedVisual)?;
+
+    let left = rect.x().max(0);
+    let top = rect.y().max(0);
+    let right =
+        (i32::from(rect.x()) + i32::from(rect.width())).min(i32::from(screen.width_in_pixels()));
+    let bottom =
+        (i32::from(rect.y()) + i32::from(rect.height())).min(i32::from(screen.height_in_pixels()));
+    let width = (right - i32::from(left)).max(0) as u16;
+    let height = (bottom - i32::from(top)).max(0) as u16;
+    if width == 0 || height == 0 {
+        return Err(HacksawError::EmptyCapture);
+    }
+
+    let reply = xcb::get_image(
+        &conn,
+        xcb::IMAGE_FORMAT_Z_PIXMAP as u8,
+        screen.root(),
+        left,
+        top,
+        width,
+        height,
+        !0,
+    )
+    .get_reply()?;
+
+    Ok(Image::from_z_pixmap(&format, reply.data(), width, height))
+}
+
+/// Extract the channel selected by `mask` from `pixel`, scaled to 8 bits.
+fn channel(pixel: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+    let value = (pixel & mask) >> mask.trailing_zeros();
+    let max = mask >> mask.trailing_zeros();
+    (u64::from(value) * 255 / u64::from(max)) as u8
+}
+
+#[test]
+fn test_pixel_format() {
+    let format = PixelFormat {
+        depth: 24,
+        bits_per_pixel: 32,
+        scanline_pad: 32,
+        lsb_first: true,
+        red_mask: 0xFF_00_00,
+        green_mask: 0x00_FF_00,
+        blue_mask: 0x00_00_FF,
+    };
+    // BGRX in memory
+    let data = [0x30, 0x20, 0x10, 0x00, 0x01, 0x02, 0x03, 0x00];
+    assert_eq!(format.pixel(&data, 2, 1, 0), 0x03_02_01);
+    assert_eq!(format.rgb(format.pixel(&data, 2, 0, 0)), [0x10, 0x20, 0x30]);
+
+    let mut copy = [0; 8];
+    format.put_pixel(&mut copy, 2, 0, 0, 0x10_20_30);
+    format.put_pixel(&mut copy, 2, 1, 0, 0x03_02_01);
+    assert_eq!(copy, data);
+
+    // 16-bit RGB565 with rows padded to 32 bits
+    let format = PixelFormat {
+        depth: 16,
+        bits_per_pixel: 16,
+        scanline_pad: 32,
+        lsb_first: true,
+        red_mask: 0xF8_00,
+        green_mask: 0x07_E0,
+        blue_mask: 0x00_1F,
+    };
+    assert_eq!(format.stride(3), 8);
+    assert_eq!(format.rgb(0xFF_FF), [0xFF, 0xFF, 0xFF]);
+    assert_eq!(format.rgb(0xF8_00), [0xFF, 0x00, 0x00]);
+}
+
+#[test]
+fn test_write_ppm() {
+    let image = Image {
+        width: 2,
+        height: 1,
+        pixels: vec![1, 2, 3, 4, 5, 6],
+    };
+    let mut out = vec![];
+    image.write_ppm(&mut out).unwrap();
+    assert_eq!(out, b"P6\n2 1\n255\n\x01\x02\x03\x04\x05\x06");
+}
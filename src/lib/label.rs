@@ -0,0 +1,111 @@
+//! A small text box following the pointer, drawn with a core X font.
+
+use super::overlay::{place, Corner, Font, PADDING};
+use super::{set_title, HacksawError};
+
+pub struct Label {
+    window: xcb::Window,
+    gc: xcb::Gcontext,
+    char_width: u16,
+    ascent: i16,
+    height: u16,
+    text: String,
+    mapped: bool,
+}
+
+impl Label {
+    pub fn new(conn: &xcb::Connection, screen: &xcb::Screen) -> Result<Label, HacksawError> {
+        let font = Font::open(conn)?;
+
+        let window = conn.generate_id();
+        let values = [
+            (xcb::CW_BACK_PIXEL, screen.black_pixel()),
+            (xcb::CW_OVERRIDE_REDIRECT, 1u32),
+            (xcb::CW_EVENT_MASK, xcb::EVENT_MASK_EXPOSURE),
+        ];
+        xcb::create_window(
+            conn,
+            xcb::COPY_FROM_PARENT as u8,
+            window,
+            screen.root(),
+            0,
+            0,
+            1,
+            1,
+            0,
+            xcb::WINDOW_CLASS_INPUT_OUTPUT as u16,
+            screen.root_visual(),
+            &values,
+        );
+        set_title(conn, window, "hacksaw");
+
+        Ok(Label {
+            window,
+            char_width: font.char_width,
+            ascent: font.ascent,
+            height: font.line_height(),
+            gc: font.create_gc(conn, screen, window),
+            text: String::new(),
+            mapped: false,
+        })
+    }
+
+    pub fn window(&self) -> xcb::Window {
+        self.window
+    }
+
+    /// Show `text` next to `cursor`, keeping the label within `bounds`.
+    pub fn show(
+        &mut self,
+        conn: &xcb::Connection,
+        text: String,
+        cursor: xcb::Point,
+        bounds: xcb::Rectangle,
+    ) {
+        let width = self.char_width * text.len() as u16 + 2 * PADDING;
+        let pos = place(bounds, cursor, width, self.height, Corner::BottomRight);
+        xcb::configure_window(
+            conn,
+            self.window,
+            &[
+                (xcb::CONFIG_WINDOW_X as u16, pos.x() as u32),
+                (xcb::CONFIG_WINDOW_Y as u16, pos.y() as u32),
+                (xcb::CONFIG_WINDOW_WIDTH as u16, u32::from(width)),
+                (xcb::CONFIG_WINDOW_HEIGHT as u16, u32::from(self.height)),
+                (xcb::CONFIG_WINDOW_STACK_MODE as u16, xcb::STACK_MODE_ABOVE),
+            ],
+        );
+        if !self.mapped {
+            xcb::map_window(conn, self.window);
+            self.mapped = true;
+        }
+
+        self.text = text;
+        self.redraw(conn);
+    }
+
+    pub fn hide(&mut self, conn: &xcb::Connection) {
+        if self.mapped {
+            xcb::unmap_window(conn, self.window);
+            self.mapped = false;
+        }
+    }
+
+    /// Draw the text again, e.g. after the window got exposed.
+    pub fn redraw(&self, conn: &xcb::Connection) {
+        xcb::clear_area(conn, false, self.window, 0, 0, 0, 0);
+        xcb::image_text_8(
+            conn,
+            self.window,
+            self.gc,
+            PADDING as i16,
+            PADDING as i16 + self.ascent,
+            &self.text,
+        );
+    }
+
+    pub fn destroy(self, conn: &xcb::Connection) {
+        xcb::free_gc(conn, self.gc);
+        xcb::destroy_window(conn, self.window);
+    }
+}
@@ -0,0 +1,198 @@
+//! A loupe following the pointer, showing the pixels around it zoomed in.
+
+use super::image::PixelFormat;
+use super::overlay::{place, Corner, Font, PADDING};
+use super::{set_title, HacksawError};
+
+/// Number of screen pixels shown on each side of the one under the pointer.
+const RADIUS: u16 = 7;
+const GRID: u16 = 2 * RADIUS + 1;
+
+pub struct Magnifier {
+    window: xcb::Window,
+    gc: xcb::Gcontext,
+    zoom: u16,
+    format: PixelFormat,
+    /// The screen contents from before any of our windows were mapped, so
+    /// that they don't show up in the loupe.
+    snapshot: Vec<u8>,
+    screen: xcb::Rectangle,
+    ascent: i16,
+    text_height: u16,
+    black: u32,
+    white: u32,
+}
+
+impl Magnifier {
+    /// Take a snapshot of the screen and create the (unmapped) loupe. This
+    /// has to happen before any other overlay is mapped.
+    pub fn new(
+        conn: &xcb::Connection,
+        screen: &xcb::Screen,
+        format: PixelFormat,
+        zoom: u16,
+    ) -> Result<Magnifier, HacksawError> {
+        let screen_rect =
+            xcb::Rectangle::new(0, 0, screen.width_in_pixels(), screen.height_in_pixels());
+        let snapshot = xcb::get_image(
+            conn,
+            xcb::IMAGE_FORMAT_Z_PIXMAP as u8,
+            screen.root(),
+            0,
+            0,
+            screen_rect.width(),
+            screen_rect.height(),
+            !0,
+        )
+        .get_reply()?
+        .data()
+        .to_vec();
+
+        let font = Font::open(conn)?;
+        let text_height = font.line_height();
+
+        let size = GRID * zoom;
+        let window = conn.generate_id();
+        let values = [
+            (xcb::CW_BACK_PIXEL, screen.black_pixel()),
+            (xcb::CW_BORDER_PIXEL, screen.white_pixel()),
+            (xcb::CW_OVERRIDE_REDIRECT, 1u32),
+        ];
+        xcb::create_window(
+            conn,
+            xcb::COPY_FROM_PARENT as u8,
+            window,
+            screen.root(),
+            0,
+            0,
+            size,
+            size + text_height,
+            1,
+            xcb::WINDOW_CLASS_INPUT_OUTPUT as u16,
+            screen.root_visual(),
+            &values,
+        );
+        set_title(conn, window, "hacksaw");
+
+        Ok(Magnifier {
+            window,
+            zoom,
+            format,
+            snapshot,
+            screen: screen_rect,
+            ascent: font.ascent,
+            gc: font.create_gc(conn, screen, window),
+            text_height,
+            black: screen.black_pixel(),
+            white: screen.white_pixel(),
+        })
+    }
+
+    pub fn window(&self) -> xcb::Window {
+        self.window
+    }
+
+    fn snapshot_pixel(&self, x: i32, y: i32) -> Option<u32> {
+        if x < 0
+            || y < 0
+            || x >= i32::from(self.screen.width())
+            || y >= i32::from(self.screen.height())
+        {
+            return None;
+        }
+        Some(
+            self.format
+                .pixel(&self.snapshot, self.screen.width(), x as u16, y as u16),
+        )
+    }
+
+    /// Show the pixels around `cursor`, keeping the loupe within `bounds`.
+    pub fn update(&self, conn: &xcb::Connection, cursor: xcb::Point, bounds: xcb::Rectangle) {
+        let size = GRID * self.zoom;
+        // Above and to the left, clear of the label
+        let pos = place(
+            bounds,
+            cursor,
+            size + 2,
+            size + self.text_height + 2,
+            Corner::TopLeft,
+        );
+        xcb::configure_window(
+            conn,
+            self.window,
+            &[
+                (xcb::CONFIG_WINDOW_X as u16, pos.x() as u32),
+                (xcb::CONFIG_WINDOW_Y as u16, pos.y() as u32),
+                (xcb::CONFIG_WINDOW_STACK_MODE as u16, xcb::STACK_MODE_ABOVE),
+            ],
+        );
+        xcb::map_window(conn, self.window);
+
+        let mut zoomed = vec![0; self.format.stride(size) * size as usize];
+        for y in 0..size {
+            for x in 0..size {
+                let pixel = self
+                    .snapshot_pixel(
+                        i32::from(cursor.x()) - i32::from(RADIUS) + i32::from(x / self.zoom),
+                        i32::from(cursor.y()) - i32::from(RADIUS) + i32::from(y / self.zoom),
+                    )
+                    .unwrap_or(self.black);
+                self.format.put_pixel(&mut zoomed, size, x, y, pixel);
+            }
+        }
+        xcb::put_image(
+            conn,
+            xcb::IMAGE_FORMAT_Z_PIXMAP as u8,
+            self.window,
+            self.gc,
+            size,
+            size,
+            0,
+            0,
+            0,
+            self.format.depth,
+            &zoomed,
+        );
+
+        // Crosshair around the pixel under the pointer, black on white so it
+        // shows up on any background
+        let centre = (RADIUS * self.zoom) as i16;
+        for &(colour, inset) in &[(self.white, 1), (self.black, 0)] {
+            xcb::change_gc(conn, self.gc, &[(xcb::GC_FOREGROUND, colour)]);
+            xcb::poly_rectangle(
+                conn,
+                self.window,
+                self.gc,
+                &[xcb::Rectangle::new(
+                    centre - 1 + inset,
+                    centre - 1 + inset,
+                    self.zoom + 1 - 2 * inset as u16,
+                    self.zoom + 1 - 2 * inset as u16,
+                )],
+            );
+        }
+        xcb::change_gc(conn, self.gc, &[(xcb::GC_FOREGROUND, self.white)]);
+
+        let text = match self.snapshot_pixel(cursor.x().into(), cursor.y().into()) {
+            Some(pixel) => {
+                let [r, g, b] = self.format.rgb(pixel);
+                format!("#{:02x}{:02x}{:02x}", r, g, b)
+            }
+            None => String::new(),
+        };
+        xcb::clear_area(conn, false, self.window, 0, size as i16, 0, 0);
+        xcb::image_text_8(
+            conn,
+            self.window,
+            self.gc,
+            PADDING as i16,
+            size as i16 + PADDING as i16 + self.ascent,
+            &text,
+        );
+    }
+
+    pub fn destroy(self, conn: &xcb::Connection) {
+        xcb::free_gc(conn, self.gc);
+        xcb::destroy_window(conn, self.window);
+    }
+}
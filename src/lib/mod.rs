@@ -1,11 +1,45 @@
-pub mod parse_args;
+//! Interactive selection of screen regions and windows on X11.
+//!
+//! [`select`] runs the whole interaction, just like the `hacksaw` binary:
+//!
+//! ```no_run
+//! let result = hacksaw::select(&hacksaw::Options::default())?;
+//! println!("{}x{}+{}+{}", result.width(), result.height(), result.x(), result.y());
+//! # Ok::<(), hacksaw::error::HacksawError>(())
+//! ```
+//!
+//! The window query helpers are available on their own, and
+//! [`selection::SelectionState`] holds the interaction logic without any X
+//! calls.
+
+pub mod error;
+pub mod image;
+mod label;
+mod magnifier;
+mod overlay;
 pub mod parse_format;
+mod png;
+mod select;
+pub mod selection;
+
+pub use self::error::HacksawError;
+pub use self::image::{capture, Image};
+pub use self::select::{select, select_multiple, Options};
 
 use self::parse_format::FormatToken;
-use xcb::shape;
+use self::selection::round_length;
+use xcb::{randr, shape};
+
+use std::collections::VecDeque;
 
 pub const CURSOR_GRAB_TRIES: i32 = 5;
-const ESC_KEYSYM: u32 = 0xff1b;
+
+pub const ESC_KEYSYM: xcb::Keysym = 0xff1b;
+pub const RETURN_KEYSYM: xcb::Keysym = 0xff0d;
+pub const LEFT_KEYSYM: xcb::Keysym = 0xff51;
+pub const UP_KEYSYM: xcb::Keysym = 0xff52;
+pub const RIGHT_KEYSYM: xcb::Keysym = 0xff53;
+pub const DOWN_KEYSYM: xcb::Keysym = 0xff54;
 
 /// Since MOD_MASK_ANY is apparently bug-ridden, we instead exploit the fact
 /// that the modifier masks NONE to MOD_MASK_5 are 0, 1, 2, 4, 8, ... 128.
@@ -14,13 +48,135 @@ const ESC_KEYSYM: u32 = 0xff1b;
 /// CapsLock+Shift+Esc, or any other combination.
 const KEY_GRAB_MASK_MAX: xcb::ModMask = (xcb::MOD_MASK_5 * 2) - 1;
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
+pub struct Monitor {
+    pub name: String,
+    pub rect: xcb::Rectangle,
+}
+
+impl Monitor {
+    fn contains(&self, point: xcb::Point) -> bool {
+        self.rect.x() <= point.x()
+            && self.rect.y() <= point.y()
+            && point.x() - self.rect.x() < self.rect.width() as i16
+            && point.y() - self.rect.y() < self.rect.height() as i16
+    }
+}
+
+/// How a result was selected.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SelectionMode {
+    /// A rectangle was dragged out.
+    Drag,
+    /// A window was picked by clicking on it.
+    Window,
+    /// A whole monitor was picked by clicking on the desktop.
+    Monitor,
+}
+
+impl SelectionMode {
+    fn name(self) -> &'static str {
+        match self {
+            SelectionMode::Drag => "drag",
+            SelectionMode::Window => "window",
+            SelectionMode::Monitor => "monitor",
+        }
+    }
+}
+
+/// Which windows count as being in a dragged selection.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Lasso {
+    /// Windows overlapping the selection.
+    Intersecting,
+    /// Windows entirely within the selection.
+    Contained,
+}
+
+impl Lasso {
+    /// Whether `window` counts as being in `selection`.
+    pub fn matches(self, selection: xcb::Rectangle, window: xcb::Rectangle) -> bool {
+        let edges = |rect: xcb::Rectangle| {
+            (
+                i32::from(rect.x()),
+                i32::from(rect.y()),
+                i32::from(rect.x()) + i32::from(rect.width()),
+                i32::from(rect.y()) + i32::from(rect.height()),
+            )
+        };
+        let (left, top, right, bottom) = edges(selection);
+        let (win_left, win_top, win_right, win_bottom) = edges(window);
+        match self {
+            Lasso::Intersecting => {
+                win_left < right && left < win_right && win_top < bottom && top < win_bottom
+            }
+            Lasso::Contained => {
+                left <= win_left && top <= win_top && win_right <= right && win_bottom <= bottom
+            }
+        }
+    }
+}
+
+/// Which part of a client window to select when picking through EWMH.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Extents {
+    /// Only the contents, without client-side shadows as given by
+    /// `_GTK_FRAME_EXTENTS`.
+    Content,
+    /// The client window as it is.
+    Client,
+    /// The client window plus the window manager's decorations, as given by
+    /// `_NET_FRAME_EXTENTS`.
+    Frame,
+}
+
+/// Properties of a picked window, as set by its client.
+#[derive(Clone, Default)]
+pub struct WindowInfo {
+    /// First part of `WM_CLASS`.
+    pub instance: Option<String>,
+    /// Second part of `WM_CLASS`.
+    pub class: Option<String>,
+    /// `_NET_WM_NAME`, or `WM_NAME` if that is not set.
+    pub name: Option<String>,
+    /// `_NET_WM_PID`, the process the window belongs to.
+    pub pid: Option<u32>,
+    /// `_NET_WM_DESKTOP`, the virtual desktop the window is on. 0xFFFFFFFF
+    /// means all of them.
+    pub desktop: Option<u32>,
+}
+
+#[derive(Clone)]
 pub struct HacksawResult {
     pub window: u32,
     pub rect: xcb::Rectangle,
+    /// The monitor the selection (mostly) lies on, if known.
+    pub monitor: Option<Monitor>,
+    /// The whole screen, if known, for offsets from its right and bottom.
+    pub screen: Option<xcb::Rectangle>,
+    pub mode: SelectionMode,
+    /// The mouse button the selection was made with, if any.
+    pub button: Option<u8>,
+    pub info: Option<WindowInfo>,
+    /// Windows in a dragged selection, from bottom to top. Only filled in
+    /// when asked for with [`Options::lasso`].
+    pub windows: Vec<HacksawResult>,
 }
 
 impl HacksawResult {
+    pub fn new(window: u32, rect: xcb::Rectangle, mode: SelectionMode) -> HacksawResult {
+        HacksawResult {
+            window,
+            rect,
+            monitor: None,
+            screen: None,
+            mode,
+            button: None,
+            info: None,
+            windows: vec![],
+        }
+    }
+
     pub fn x(&self) -> i16 {
         self.rect.x()
     }
@@ -33,21 +189,73 @@ impl HacksawResult {
     pub fn height(&self) -> u16 {
         self.rect.height()
     }
+    pub fn right(&self) -> i32 {
+        i32::from(self.x()) + i32::from(self.width())
+    }
+    pub fn bottom(&self) -> i32 {
+        i32::from(self.y()) + i32::from(self.height())
+    }
+    pub fn area(&self) -> u32 {
+        u32::from(self.width()) * u32::from(self.height())
+    }
+
+    pub fn centre(&self) -> xcb::Point {
+        xcb::Point::new(
+            self.x() + (self.width() / 2) as i16,
+            self.y() + (self.height() / 2) as i16,
+        )
+    }
 
-    pub fn relative_to(&self, parent: HacksawResult) -> HacksawResult {
+    pub fn relative_to(&self, parent: &HacksawResult) -> HacksawResult {
         HacksawResult {
-            window: self.window,
             rect: xcb::Rectangle::new(
                 parent.x() + self.x(),
                 parent.y() + self.y(),
                 self.width(),
                 self.height(),
             ),
+            ..self.clone()
         }
     }
 
+    /// The selection geometry with its position relative to the monitor it
+    /// is on, or to the screen if there is none.
+    fn monitor_rect(&self) -> xcb::Rectangle {
+        match &self.monitor {
+            Some(monitor) => xcb::Rectangle::new(
+                self.x() - monitor.rect.x(),
+                self.y() - monitor.rect.y(),
+                self.width(),
+                self.height(),
+            ),
+            None => self.rect,
+        }
+    }
+
+    /// Round the width and height to the nearest multiples of `multiple`,
+    /// keeping the top left corner in place and the selection on the screen
+    /// if it is known.
+    pub fn round_to(&mut self, multiple: u16) {
+        self.rect = round_rect(self.rect, multiple, self.screen);
+    }
+
+    /// The selection geometry with its position given from the right and
+    /// bottom of the screen, as in X geometry `WxH-X-Y`. A selection
+    /// reaching past an edge counts as touching it, since `WxH--5-0` would
+    /// not parse.
+    fn negative_geometry(&self) -> Option<String> {
+        self.screen.map(|screen| {
+            format!(
+                "{}x{}-{}-{}",
+                self.width(),
+                self.height(),
+                (i32::from(screen.width()) - self.right()).max(0),
+                (i32::from(screen.height()) - self.bottom()).max(0),
+            )
+        })
+    }
+
     fn contains(&self, point: xcb::Point) -> bool {
-        // TODO negative x/y offsets from bottom or right?
         self.x() < point.x()
             && self.y() < point.y()
             && point.x() - self.x() <= self.width() as i16
@@ -55,44 +263,202 @@ impl HacksawResult {
     }
 
     pub fn fill_format_string(&self, format: &[FormatToken]) -> String {
+        let info = self.info.clone().unwrap_or_default();
         format
             .iter()
-            .map(|token| match token {
-                FormatToken::WindowId => self.window.to_string(),
-                FormatToken::Geometry => format!(
-                    "{}x{}+{}+{}",
-                    self.width(),
-                    self.height(),
-                    self.x(),
-                    self.y(),
-                ),
-                FormatToken::Width => self.width().to_string(),
-                FormatToken::Height => self.height().to_string(),
-                FormatToken::X => self.x().to_string(),
-                FormatToken::Y => self.y().to_string(),
-                FormatToken::Literal(s) => s.to_string(),
-            })
+            .map(|token| self.fill_token(token, &info))
             .collect::<Vec<_>>()
             .join("")
     }
+
+    fn fill_token(&self, token: &FormatToken, info: &WindowInfo) -> String {
+        match token {
+            FormatToken::WindowId => self.window.to_string(),
+            FormatToken::WindowIdHex => format!("{:#010x}", self.window),
+            FormatToken::Geometry => format_geometry(self.rect),
+            FormatToken::MonitorName => self
+                .monitor
+                .as_ref()
+                .map(|monitor| monitor.name.clone())
+                .unwrap_or_default(),
+            FormatToken::MonitorGeometry => format_geometry(self.monitor_rect()),
+            FormatToken::Width => self.width().to_string(),
+            FormatToken::Height => self.height().to_string(),
+            FormatToken::X => self.x().to_string(),
+            FormatToken::Y => self.y().to_string(),
+            FormatToken::Right => self.right().to_string(),
+            FormatToken::Bottom => self.bottom().to_string(),
+            FormatToken::Centre => {
+                let centre = self.centre();
+                format!("{},{}", centre.x(), centre.y())
+            }
+            FormatToken::Area => self.area().to_string(),
+            FormatToken::NegativeGeometry => self.negative_geometry().unwrap_or_default(),
+            FormatToken::Class => info.class.clone().unwrap_or_default(),
+            FormatToken::Instance => info.instance.clone().unwrap_or_default(),
+            FormatToken::Title => info.name.clone().unwrap_or_default(),
+            FormatToken::Pid => info.pid.map(|pid| pid.to_string()).unwrap_or_default(),
+            FormatToken::Desktop => info
+                .desktop
+                .map(|desktop| desktop.to_string())
+                .unwrap_or_default(),
+            FormatToken::Windows => self
+                .windows
+                .iter()
+                .map(|window| format!("{} {}", window.window, format_geometry(window.rect)))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            FormatToken::Json => self.to_json(),
+            FormatToken::Literal(s) => s.to_string(),
+            FormatToken::Padded(spec, token) => {
+                spec.apply(&self.fill_token(token, info), token.is_numeric())
+            }
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let info = self.info.clone().unwrap_or_default();
+        let monitor = match &self.monitor {
+            Some(monitor) => format!(
+                r#"{{"name":{},"x":{},"y":{},"width":{},"height":{}}}"#,
+                json_string(&monitor.name),
+                monitor.rect.x(),
+                monitor.rect.y(),
+                monitor.rect.width(),
+                monitor.rect.height(),
+            ),
+            None => "null".to_owned(),
+        };
+        let windows = self
+            .windows
+            .iter()
+            .map(|window| {
+                format!(
+                    r#"{{"window":{},"x":{},"y":{},"width":{},"height":{}}}"#,
+                    window.window,
+                    window.x(),
+                    window.y(),
+                    window.width(),
+                    window.height(),
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+
+        format!(
+            concat!(
+                r#"{{"x":{},"y":{},"width":{},"height":{},"window":{},"#,
+                r#""instance":{},"class":{},"name":{},"pid":{},"desktop":{},"#,
+                r#""mode":"{}","monitor":{},"button":{},"windows":[{}]}}"#,
+            ),
+            self.x(),
+            self.y(),
+            self.width(),
+            self.height(),
+            self.window,
+            json_option(info.instance.as_deref()),
+            json_option(info.class.as_deref()),
+            json_option(info.name.as_deref()),
+            json_number(info.pid),
+            json_number(info.desktop),
+            self.mode.name(),
+            monitor,
+            json_number(self.button),
+            windows,
+        )
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn json_option(s: Option<&str>) -> String {
+    s.map(json_string).unwrap_or_else(|| "null".to_owned())
+}
+
+fn json_number<T: ToString>(n: Option<T>) -> String {
+    n.map(|n| n.to_string())
+        .unwrap_or_else(|| "null".to_owned())
+}
+
+/// Round the width and height of `rect` like [`HacksawResult::round_to`].
+pub(crate) fn round_rect(
+    rect: xcb::Rectangle,
+    multiple: u16,
+    screen: Option<xcb::Rectangle>,
+) -> xcb::Rectangle {
+    let (max_width, max_height) = match screen {
+        Some(screen) => (
+            i32::from(screen.x()) + i32::from(screen.width()) - i32::from(rect.x()),
+            i32::from(screen.y()) + i32::from(screen.height()) - i32::from(rect.y()),
+        ),
+        None => (u16::MAX.into(), u16::MAX.into()),
+    };
+    xcb::Rectangle::new(
+        rect.x(),
+        rect.y(),
+        round_length(rect.width().into(), multiple, max_width) as u16,
+        round_length(rect.height().into(), multiple, max_height) as u16,
+    )
+}
+
+pub(crate) fn format_geometry(rect: xcb::Rectangle) -> String {
+    format!(
+        "{}x{}+{}+{}",
+        rect.width(),
+        rect.height(),
+        rect.x(),
+        rect.y(),
+    )
+}
+
+/// Parse a `WxH+X+Y` geometry, as printed by `%g`.
+pub fn parse_geometry(input: &str) -> Result<xcb::Rectangle, String> {
+    let parse = || -> Option<xcb::Rectangle> {
+        let (size, position) = input.split_at(input.find('+')?);
+        let (width, height) = size.split_at(size.find('x')?);
+        let (x, y) = position[1..].split_at(position[1..].find('+')?);
+        Some(xcb::Rectangle::new(
+            x.parse().ok()?,
+            y[1..].parse().ok()?,
+            width.parse().ok()?,
+            height[1..].parse().ok()?,
+        ))
+    };
+    parse().ok_or_else(|| format!("Could not parse \"{}\": expected WxH+X+Y", input))
 }
 
 pub fn set_shape(conn: &xcb::Connection, window: xcb::Window, rects: &[xcb::Rectangle]) {
     shape::rectangles(
-        &conn,
+        conn,
         shape::SO_SET as u8,
         shape::SK_BOUNDING as u8,
         0,
         window,
         0,
         0,
-        &rects,
+        rects,
     );
 }
 
 pub fn set_title(conn: &xcb::Connection, window: xcb::Window, title: &str) {
     xcb::change_property(
-        &conn,
+        conn,
         xcb::PROP_MODE_REPLACE as u8,
         window,
         xcb::ATOM_WM_NAME,
@@ -102,18 +468,18 @@ pub fn set_title(conn: &xcb::Connection, window: xcb::Window, title: &str) {
     );
 }
 
-pub fn grab_pointer_set_cursor(conn: &xcb::Connection, root: u32) -> bool {
+pub fn grab_pointer_set_cursor(conn: &xcb::Connection, root: u32) -> Result<(), HacksawError> {
     let font = conn.generate_id();
-    xcb::open_font(&conn, font, "cursor");
+    xcb::open_font(conn, font, "cursor");
 
     // TODO: create cursor with a Pixmap
     // https://stackoverflow.com/questions/40578969/how-to-create-a-cursor-in-x11-from-raw-data-c
     let cursor = conn.generate_id();
-    xcb::create_glyph_cursor(&conn, cursor, font, font, 0, 30, 0, 0, 0, 0, 0, 0);
+    xcb::create_glyph_cursor(conn, cursor, font, font, 0, 30, 0, 0, 0, 0, 0, 0);
 
     for i in 0..CURSOR_GRAB_TRIES {
         let reply = xcb::grab_pointer(
-            &conn,
+            conn,
             true,
             root,
             (xcb::EVENT_MASK_BUTTON_RELEASE
@@ -126,116 +492,747 @@ pub fn grab_pointer_set_cursor(conn: &xcb::Connection, root: u32) -> bool {
             cursor,
             xcb::CURRENT_TIME,
         )
-        .get_reply()
-        .unwrap();
+        .get_reply()?;
 
         if reply.status() as u32 == xcb::GRAB_STATUS_SUCCESS {
-            return true;
+            return Ok(());
         } else if i < CURSOR_GRAB_TRIES - 1 {
             std::thread::sleep(std::time::Duration::from_millis(100));
         }
     }
 
-    false
+    Err(HacksawError::GrabFailed)
 }
 
-pub fn find_escape_keycode(conn: &xcb::Connection) -> xcb::Keycode {
+/// Find the first keycode which produces `keysym` in the current keyboard
+/// mapping, if any.
+pub fn find_keycode(
+    conn: &xcb::Connection,
+    keysym: xcb::Keysym,
+) -> Result<Option<xcb::Keycode>, HacksawError> {
     // https://stackoverflow.com/questions/18689863/obtain-keyboard-layout-and-keysyms-with-xcb
     let setup = conn.get_setup();
     let cookie = xcb::get_keyboard_mapping(
-        &conn,
+        conn,
         setup.min_keycode(),
         setup.max_keycode() - setup.min_keycode() + 1,
     );
-    let reply = cookie.get_reply().expect("failed to get keyboard mapping");
+    let reply = cookie.get_reply()?;
 
-    let escape_index = reply
+    Ok(reply
         .keysyms()
         .iter()
-        .position(|&keysym| keysym == ESC_KEYSYM)
-        .expect("failed to find escape keysym");
-    (escape_index / reply.keysyms_per_keycode() as usize) as u8 + setup.min_keycode()
+        .position(|&sym| sym == keysym)
+        .map(|index| (index / reply.keysyms_per_keycode() as usize) as u8 + setup.min_keycode()))
 }
 
-pub fn grab_key(conn: &xcb::Connection, root: u32, keycode: u8) {
-    for mask in 0..=KEY_GRAB_MASK_MAX {
-        xcb::grab_key(
-            &conn,
-            true,
-            root,
-            mask as u16,
-            keycode,
-            xcb::GRAB_MODE_ASYNC as u8,
-            xcb::GRAB_MODE_ASYNC as u8,
-        );
+pub fn grab_keys(conn: &xcb::Connection, root: u32, keycodes: &[xcb::Keycode]) {
+    for &keycode in keycodes {
+        for mask in 0..=KEY_GRAB_MASK_MAX {
+            xcb::grab_key(
+                conn,
+                true,
+                root,
+                mask as u16,
+                keycode,
+                xcb::GRAB_MODE_ASYNC as u8,
+                xcb::GRAB_MODE_ASYNC as u8,
+            );
+        }
+    }
+}
+
+pub fn ungrab_keys(conn: &xcb::Connection, root: u32, keycodes: &[xcb::Keycode]) {
+    for &keycode in keycodes {
+        for mask in 0..=KEY_GRAB_MASK_MAX {
+            xcb::ungrab_key(conn, keycode, root, mask as u16);
+        }
     }
 }
 
-pub fn ungrab_key(conn: &xcb::Connection, root: u32, keycode: u8) {
-    for mask in 0..=KEY_GRAB_MASK_MAX {
-        xcb::ungrab_key(&conn, keycode, root, mask as u16);
+/// Whether a compositing manager is running on screen `screen_num`, so that
+/// translucent windows actually look translucent.
+pub fn compositor_running(conn: &xcb::Connection, screen_num: i32) -> bool {
+    let atom = match intern_atom(conn, &format!("_NET_WM_CM_S{}", screen_num)) {
+        Some(atom) => atom,
+        None => return false,
+    };
+    match xcb::get_selection_owner(conn, atom).get_reply() {
+        Ok(reply) => reply.owner() != xcb::NONE,
+        Err(_) => false,
     }
 }
 
-fn viewable(conn: &xcb::Connection, win: xcb::Window) -> bool {
-    let attrs = xcb::get_window_attributes(conn, win).get_reply().unwrap();
-    (attrs.map_state() & xcb::MAP_STATE_VIEWABLE as u8) != 0
+/// Find a 32-bit TrueColor visual, which gives windows an alpha channel.
+pub fn find_argb_visual(screen: &xcb::Screen) -> Option<xcb::Visualid> {
+    screen
+        .allowed_depths()
+        .filter(|depth| depth.depth() == 32)
+        .flat_map(|depth| depth.visuals())
+        .find(|visual| visual.class() == xcb::VISUAL_CLASS_TRUE_COLOR as u8)
+        .map(|visual| visual.visual_id())
 }
 
-pub fn input_output(conn: &xcb::Connection, win: xcb::Window) -> bool {
-    let attrs = xcb::get_window_attributes(conn, win).get_reply().unwrap();
-    (attrs.class() & xcb::WINDOW_CLASS_INPUT_OUTPUT as u16) != 0
+/// Convert an ARGB colour to the premultiplied form X expects for pixels of
+/// 32-bit visuals.
+pub fn premultiply(argb: u32) -> u32 {
+    let alpha = argb >> 24;
+    let channel = |shift: u32| ((argb >> shift & 0xFF) * alpha / 0xFF) << shift;
+    alpha << 24 | channel(16) | channel(8) | channel(0)
 }
 
-pub fn get_window_geom(conn: &xcb::Connection, win: xcb::Window) -> HacksawResult {
-    let geom = xcb::get_geometry(conn, win).get_reply().unwrap();
+fn intern_atom(conn: &xcb::Connection, name: &str) -> Option<xcb::Atom> {
+    atom_reply(xcb::intern_atom(conn, true, name))
+}
+
+/// Wait for an atom interned with `only_if_exists`.
+fn atom_reply(cookie: xcb::InternAtomCookie) -> Option<xcb::Atom> {
+    match cookie.get_reply().ok()?.atom() {
+        xcb::ATOM_NONE => None,
+        atom => Some(atom),
+    }
+}
+
+/// Read a property of `win` made of 32-bit values, of any type.
+fn get_u32_property(
+    conn: &xcb::Connection,
+    win: xcb::Window,
+    property: xcb::Atom,
+) -> Option<Vec<u32>> {
+    u32_property_reply(xcb::get_property(
+        conn,
+        false,
+        win,
+        property,
+        xcb::ATOM_ANY,
+        0,
+        u32::MAX / 4,
+    ))
+}
+
+/// Wait for a property made of 32-bit values.
+fn u32_property_reply(cookie: xcb::GetPropertyCookie) -> Option<Vec<u32>> {
+    let reply = cookie.get_reply().ok()?;
+    if reply.format() != 32 {
+        return None;
+    }
+    Some(reply.value::<u32>().to_vec())
+}
+
+/// Read a string-ish property of `win`, of any type.
+fn get_string_property(
+    conn: &xcb::Connection,
+    win: xcb::Window,
+    property: xcb::Atom,
+) -> Option<Vec<u8>> {
+    let reply = xcb::get_property(conn, false, win, property, xcb::ATOM_ANY, 0, 1024)
+        .get_reply()
+        .ok()?;
+    if reply.format() != 8 || reply.value_len() == 0 {
+        return None;
+    }
+    Some(reply.value::<u8>().to_vec())
+}
+
+/// The client window of `win`: `win` itself or its first descendant, breadth
+/// first, with `WM_STATE` set, like xprop and xdotool look for it. Window
+/// manager frames don't carry the client's properties. Falls back to `win`.
+pub fn find_client(conn: &xcb::Connection, win: xcb::Window) -> xcb::Window {
+    let wm_state = match intern_atom(conn, "WM_STATE") {
+        Some(atom) => atom,
+        None => return win,
+    };
+    let has_state = |win| {
+        xcb::get_property(conn, false, win, wm_state, xcb::ATOM_ANY, 0, 0)
+            .get_reply()
+            .map(|reply| reply.type_() != xcb::ATOM_NONE)
+            .unwrap_or(false)
+    };
+
+    let mut queue = VecDeque::from(vec![win]);
+    while let Some(next) = queue.pop_front() {
+        if has_state(next) {
+            return next;
+        }
+        if let Ok(tree) = xcb::query_tree(conn, next).get_reply() {
+            queue.extend(tree.children());
+        }
+    }
+    win
+}
+
+/// Properties of the client window of `win`, see [`find_client`].
+pub fn get_window_info(conn: &xcb::Connection, win: xcb::Window) -> WindowInfo {
+    let win = find_client(conn, win);
+    let mut info = WindowInfo::default();
+
+    if let Some(wm_class) = get_string_property(conn, win, xcb::ATOM_WM_CLASS) {
+        let mut parts = wm_class
+            .split(|&c| c == 0)
+            .map(|part| String::from_utf8_lossy(part).into_owned());
+        info.instance = parts.next();
+        info.class = parts.next();
+    }
+
+    info.name = intern_atom(conn, "_NET_WM_NAME")
+        .and_then(|atom| get_string_property(conn, win, atom))
+        .or_else(|| get_string_property(conn, win, xcb::ATOM_WM_NAME))
+        .map(|name| String::from_utf8_lossy(&name).into_owned());
+
+    let cardinal = |name| {
+        intern_atom(conn, name)
+            .and_then(|atom| get_u32_property(conn, win, atom))
+            .and_then(|values| values.first().copied())
+    };
+    info.pid = cardinal("_NET_WM_PID");
+    info.desktop = cardinal("_NET_WM_DESKTOP");
 
-    HacksawResult {
-        window: win,
-        rect: xcb::Rectangle::new(
+    info
+}
+
+pub fn input_output(conn: &xcb::Connection, win: xcb::Window) -> Result<bool, HacksawError> {
+    let attrs = xcb::get_window_attributes(conn, win).get_reply()?;
+    Ok((attrs.class() & xcb::WINDOW_CLASS_INPUT_OUTPUT as u16) != 0)
+}
+
+pub fn get_window_geom(
+    conn: &xcb::Connection,
+    win: xcb::Window,
+) -> Result<HacksawResult, HacksawError> {
+    let geom = xcb::get_geometry(conn, win).get_reply()?;
+    Ok(window_geom(win, &geom))
+}
+
+/// The geometry of `win` relative to its parent, borders included.
+fn window_geom(win: xcb::Window, geom: &xcb::GetGeometryReply) -> HacksawResult {
+    HacksawResult::new(
+        win,
+        xcb::Rectangle::new(
             geom.x(),
             geom.y(),
             geom.width() + 2 * geom.border_width(),
             geom.height() + 2 * geom.border_width(),
         ),
-    }
+        SelectionMode::Window,
+    )
 }
 
-pub fn get_window_at_point(
+/// List the visible children of `win` from bottom to top, skipping any
+/// window in `ignore` (such as our own overlay).
+///
+/// Like [`get_clients`], this sends the requests for all children before
+/// waiting on any of the replies.
+pub fn get_windows(
     conn: &xcb::Connection,
     win: xcb::Window,
-    pt: xcb::Point,
-    remove_decorations: u32,
-) -> Option<HacksawResult> {
-    let tree = xcb::query_tree(conn, win).get_reply().unwrap();
-    let children = tree
+    ignore: &[xcb::Window],
+) -> Result<Vec<HacksawResult>, HacksawError> {
+    let tree = xcb::query_tree(conn, win).get_reply()?;
+    let cookies = tree
         .children()
         .iter()
-        .filter(|&child| viewable(conn, *child))
-        .filter(|&child| input_output(conn, *child))
-        .filter_map(|&child| {
-            let geom = get_window_geom(conn, child);
-            if geom.contains(pt) {
-                Some(geom)
-            } else {
-                None
-            }
+        .filter(|child| !ignore.contains(child))
+        .map(|&child| {
+            (
+                child,
+                xcb::get_window_attributes(conn, child),
+                xcb::get_geometry(conn, child),
+            )
         })
         .collect::<Vec<_>>();
 
-    if children.is_empty() {
-        return None;
+    let mut windows = vec![];
+    for (child, attrs, geom) in cookies {
+        if let Some(window) = skip_vanished(child_geom(child, attrs, geom))? {
+            windows.push(window);
+        }
+    }
+
+    Ok(windows)
+}
+
+/// Wait for the geometry of a child window. `None` if it is not visible or
+/// only takes input.
+fn child_geom(
+    child: xcb::Window,
+    attrs: xcb::GetWindowAttributesCookie,
+    geom: xcb::GetGeometryCookie,
+) -> Result<Option<HacksawResult>, HacksawError> {
+    let attrs = attrs.get_reply()?;
+    let viewable = (attrs.map_state() & xcb::MAP_STATE_VIEWABLE as u8) != 0;
+    let input_output = (attrs.class() & xcb::WINDOW_CLASS_INPUT_OUTPUT as u16) != 0;
+    if !viewable || !input_output {
+        return Ok(None);
+    }
+    Ok(Some(window_geom(child, &geom.get_reply()?)))
+}
+
+/// Treat a window that vanished while listing it like one that was never
+/// there, so that only the window actually picked can fail that way.
+fn skip_vanished<T>(result: Result<Option<T>, HacksawError>) -> Result<Option<T>, HacksawError> {
+    match result {
+        Err(HacksawError::WindowVanished) => Ok(None),
+        result => result,
+    }
+}
+
+/// Find the topmost window under `pt` among the children of `win`, skipping
+/// any window in `ignore` (such as our own overlay).
+pub fn get_window_at_point(
+    conn: &xcb::Connection,
+    win: xcb::Window,
+    pt: xcb::Point,
+    remove_decorations: u32,
+    ignore: &[xcb::Window],
+) -> Result<Option<HacksawResult>, HacksawError> {
+    let topmost = get_windows(conn, win, ignore)?
+        .into_iter()
+        .rev()
+        .find(|window| window.contains(pt));
+
+    match topmost {
+        Some(window) => Ok(Some(remove_frames(conn, window, remove_decorations)?)),
+        None => Ok(None),
     }
+}
+
+/// The windows under `pt`, from `top` down to the deepest viewable child of
+/// it, all positioned relative to `root`.
+pub fn get_window_path_at_point(
+    conn: &xcb::Connection,
+    root: xcb::Window,
+    top: HacksawResult,
+    pt: xcb::Point,
+    ignore: &[xcb::Window],
+) -> Result<Vec<HacksawResult>, HacksawError> {
+    // Children are placed from where the window really is, since `top` may
+    // have been grown or shrunk by its frame extents
+    let origin = xcb::translate_coordinates(conn, top.window, root, 0, 0).get_reply()?;
+    let mut parent = HacksawResult::new(
+        top.window,
+        xcb::Rectangle::new(origin.dst_x(), origin.dst_y(), top.width(), top.height()),
+        top.mode,
+    );
+
+    let mut path = vec![top];
+    loop {
+        let child = get_windows(conn, parent.window, ignore)?
+            .into_iter()
+            .map(|child| child.relative_to(&parent))
+            .rev()
+            .find(|child| child.contains(pt));
+        match child {
+            Some(child) => {
+                parent = child.clone();
+                path.push(child);
+            }
+            None => break,
+        }
+    }
+
+    Ok(path)
+}
 
-    let mut window = children[children.len() - 1];
-    for _ in 0..remove_decorations {
-        let tree = xcb::query_tree(conn, window.window).get_reply().unwrap();
+/// Replace `window` by its first child, up to `count` times, to get from a
+/// window manager frame to the client window inside it.
+fn remove_frames(
+    conn: &xcb::Connection,
+    mut window: HacksawResult,
+    count: u32,
+) -> Result<HacksawResult, HacksawError> {
+    for _ in 0..count {
+        let tree = xcb::query_tree(conn, window.window).get_reply()?;
         if tree.children_len() == 0 {
             break;
         }
         let firstborn = tree.children()[0];
-        window = get_window_geom(conn, firstborn).relative_to(window);
+        window = get_window_geom(conn, firstborn)?.relative_to(&window);
     }
 
-    Some(window)
+    Ok(window)
+}
+
+/// List the visible children of `win` that are in `selection`, from bottom
+/// to top, skipping any window in `ignore`.
+pub fn get_windows_in(
+    conn: &xcb::Connection,
+    win: xcb::Window,
+    selection: xcb::Rectangle,
+    lasso: Lasso,
+    remove_decorations: u32,
+    ignore: &[xcb::Window],
+) -> Result<Vec<HacksawResult>, HacksawError> {
+    let mut windows = vec![];
+    for window in get_windows(conn, win, ignore)? {
+        let window = remove_frames(conn, window, remove_decorations).map(Some);
+        match skip_vanished(window)? {
+            Some(window) if lasso.matches(selection, window.rect) => windows.push(window),
+            _ => (),
+        }
+    }
+    Ok(windows)
+}
+
+/// Grow `rect` by `extents` (left, right, top, bottom), or shrink it if
+/// `grow` is false.
+fn apply_extents(rect: xcb::Rectangle, extents: &[u32], grow: bool) -> xcb::Rectangle {
+    let (left, right, top, bottom) = match *extents {
+        [left, right, top, bottom] => (left as i32, right as i32, top as i32, bottom as i32),
+        _ => return rect,
+    };
+    let sign = if grow { 1 } else { -1 };
+    let width = i32::from(rect.width()) + sign * (left + right);
+    let height = i32::from(rect.height()) + sign * (top + bottom);
+    xcb::Rectangle::new(
+        (i32::from(rect.x()) - sign * left) as i16,
+        (i32::from(rect.y()) - sign * top) as i16,
+        width.max(0) as u16,
+        height.max(0) as u16,
+    )
+}
+
+/// Wait for the geometry of a client window relative to the root window.
+/// `None` if it is not visible.
+fn client_rect(
+    attrs: xcb::GetWindowAttributesCookie,
+    geom: xcb::GetGeometryCookie,
+    origin: xcb::TranslateCoordinatesCookie,
+) -> Result<Option<xcb::Rectangle>, HacksawError> {
+    let attrs = attrs.get_reply()?;
+    let geom = geom.get_reply()?;
+    let origin = origin.get_reply()?;
+    if (attrs.map_state() & xcb::MAP_STATE_VIEWABLE as u8) == 0 {
+        return Ok(None);
+    }
+    Ok(Some(xcb::Rectangle::new(
+        origin.dst_x(),
+        origin.dst_y(),
+        geom.width(),
+        geom.height(),
+    )))
+}
+
+/// List the visible client windows from bottom to top, according to the
+/// window manager's `_NET_CLIENT_LIST_STACKING`, with `extents` applied and
+/// skipping any window in `ignore`. Returns `None` if the window manager does
+/// not provide the list.
+///
+/// The requests for all clients are sent before waiting on any of the
+/// replies, so this takes a handful of round trips however many clients
+/// there are.
+pub fn get_clients(
+    conn: &xcb::Connection,
+    root: xcb::Window,
+    extents: Extents,
+    ignore: &[xcb::Window],
+) -> Result<Option<Vec<HacksawResult>>, HacksawError> {
+    let (property, grow) = match extents {
+        Extents::Content => (Some("_GTK_FRAME_EXTENTS"), false),
+        Extents::Client => (None, false),
+        Extents::Frame => (Some("_NET_FRAME_EXTENTS"), true),
+    };
+    let stacking = xcb::intern_atom(conn, true, "_NET_CLIENT_LIST_STACKING");
+    let property = property.map(|name| xcb::intern_atom(conn, true, name));
+    let stacking = match atom_reply(stacking).and_then(|atom| get_u32_property(conn, root, atom)) {
+        Some(stacking) => stacking,
+        None => return Ok(None),
+    };
+    let property = property.and_then(atom_reply);
+
+    let cookies = stacking
+        .into_iter()
+        .filter(|client| !ignore.contains(client))
+        .map(|client| {
+            (
+                client,
+                xcb::get_window_attributes(conn, client),
+                xcb::get_geometry(conn, client),
+                xcb::translate_coordinates(conn, client, root, 0, 0),
+                property.map(|property| {
+                    xcb::get_property(conn, false, client, property, xcb::ATOM_ANY, 0, 4)
+                }),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut clients = vec![];
+    for (client, attrs, geom, origin, extents) in cookies {
+        let rect = match skip_vanished(client_rect(attrs, geom, origin))? {
+            Some(rect) => rect,
+            None => continue,
+        };
+        let rect = match extents.and_then(u32_property_reply) {
+            Some(values) => apply_extents(rect, &values, grow),
+            None => rect,
+        };
+        clients.push(HacksawResult::new(client, rect, SelectionMode::Window));
+    }
+
+    Ok(Some(clients))
+}
+
+/// Query the active monitors through RandR. Outputs that mirror each other
+/// are only listed once, and nothing is returned if RandR is unavailable.
+pub fn get_monitors(conn: &xcb::Connection, root: xcb::Window) -> Vec<Monitor> {
+    let resources = match randr::get_screen_resources_current(conn, root).get_reply() {
+        Ok(resources) => resources,
+        Err(_) => return vec![],
+    };
+    let timestamp = resources.config_timestamp();
+
+    let mut monitors: Vec<Monitor> = vec![];
+    for &output in resources.outputs() {
+        let output = match randr::get_output_info(conn, output, timestamp).get_reply() {
+            Ok(output) => output,
+            Err(_) => continue,
+        };
+        if output.connection() != randr::CONNECTION_CONNECTED as u8 || output.crtc() == xcb::NONE {
+            continue;
+        }
+        let crtc = match randr::get_crtc_info(conn, output.crtc(), timestamp).get_reply() {
+            Ok(crtc) => crtc,
+            Err(_) => continue,
+        };
+        if monitors
+            .iter()
+            .any(|monitor| monitor.rect.x() == crtc.x() && monitor.rect.y() == crtc.y())
+        {
+            continue;
+        }
+        monitors.push(Monitor {
+            name: String::from_utf8_lossy(output.name()).into_owned(),
+            rect: xcb::Rectangle::new(crtc.x(), crtc.y(), crtc.width(), crtc.height()),
+        });
+    }
+
+    monitors
+}
+
+pub fn find_monitor(monitors: &[Monitor], point: xcb::Point) -> Option<&Monitor> {
+    monitors.iter().find(|monitor| monitor.contains(point))
+}
+
+#[test]
+fn test_premultiply() {
+    assert_eq!(premultiply(0xFF_12_34_56), 0xFF_12_34_56);
+    assert_eq!(premultiply(0x80_FF_80_00), 0x80_80_40_00);
+    assert_eq!(premultiply(0x00_FF_FF_FF), 0);
+}
+
+#[test]
+fn test_parse_geometry() {
+    let parsed = |input| parse_geometry(input).map(format_geometry);
+
+    assert_eq!(parsed("640x480+10+20"), Ok("640x480+10+20".to_owned()));
+    // Whatever %g prints can be read back
+    let rect = xcb::Rectangle::new(-5, 7, 100, 50);
+    assert_eq!(parsed(&format_geometry(rect)), Ok("100x50+-5+7".to_owned()));
+
+    assert!(parse_geometry("640x480").is_err());
+    assert!(parse_geometry("640x480+10").is_err());
+    assert!(parse_geometry("640+480+10+20").is_err());
+    assert!(parse_geometry("-640x480+10+20").is_err());
+}
+
+/// A picked xterm window, partly above the top of the screen.
+#[cfg(test)]
+fn test_result() -> HacksawResult {
+    let mut result = HacksawResult::new(
+        0x1e00003,
+        xcb::Rectangle::new(10, -5, 300, 200),
+        SelectionMode::Window,
+    );
+    result.button = Some(1);
+    result.info = Some(WindowInfo {
+        instance: Some("xterm".into()),
+        class: Some("XTerm".into()),
+        name: Some("say \"hi\"\n".into()),
+        pid: Some(4242),
+        desktop: None,
+    });
+    result.monitor = Some(Monitor {
+        name: "DP-1".into(),
+        rect: xcb::Rectangle::new(0, 0, 1920, 1080),
+    });
+    result
+}
+
+#[test]
+fn test_to_json() {
+    let result = test_result();
+    assert_eq!(
+        result.to_json(),
+        concat!(
+            r#"{"x":10,"y":-5,"width":300,"height":200,"window":31457283,"#,
+            r#""instance":"xterm","class":"XTerm","name":"say \"hi\"\n","pid":4242,"desktop":null,"#,
+            r#""mode":"window","monitor":{"name":"DP-1","x":0,"y":0,"width":1920,"height":1080},"#,
+            r#""button":1,"windows":[]}"#,
+        )
+    );
+
+    let result = HacksawResult::new(1, xcb::Rectangle::new(0, 0, 1, 1), SelectionMode::Drag);
+    assert_eq!(
+        result.to_json(),
+        concat!(
+            r#"{"x":0,"y":0,"width":1,"height":1,"window":1,"#,
+            r#""instance":null,"class":null,"name":null,"pid":null,"desktop":null,"#,
+            r#""mode":"drag","monitor":null,"button":null,"windows":[]}"#,
+        )
+    );
+
+    let mut result =
+        HacksawResult::new(1, xcb::Rectangle::new(0, 0, 100, 100), SelectionMode::Drag);
+    result.windows = vec![
+        HacksawResult::new(2, xcb::Rectangle::new(0, 0, 10, 10), SelectionMode::Window),
+        HacksawResult::new(3, xcb::Rectangle::new(5, 5, 10, 10), SelectionMode::Window),
+    ];
+    assert!(result.to_json().ends_with(concat!(
+        r#""windows":[{"window":2,"x":0,"y":0,"width":10,"height":10},"#,
+        r#"{"window":3,"x":5,"y":5,"width":10,"height":10}]}"#,
+    )));
+    assert_eq!(
+        result.fill_format_string(&[FormatToken::Windows]),
+        "2 10x10+0+0\n3 10x10+5+5"
+    );
+}
+
+#[test]
+fn test_window_info_tokens() {
+    let tokens = [
+        FormatToken::Instance,
+        FormatToken::Literal(".".into()),
+        FormatToken::Class,
+        FormatToken::Literal(" ".into()),
+        FormatToken::Pid,
+        FormatToken::Literal(" ".into()),
+        FormatToken::Desktop,
+        FormatToken::Literal(" ".into()),
+        FormatToken::Title,
+    ];
+    assert_eq!(
+        test_result().fill_format_string(&tokens),
+        "xterm.XTerm 4242  say \"hi\"\n"
+    );
+
+    // Nothing is known about dragged selections
+    let result = HacksawResult::new(1, xcb::Rectangle::new(0, 0, 1, 1), SelectionMode::Drag);
+    assert_eq!(
+        result.fill_format_string(&[FormatToken::Pid, FormatToken::Class]),
+        ""
+    );
+}
+
+#[test]
+fn test_padded_tokens() {
+    let fill = |result: &HacksawResult, format| {
+        result.fill_format_string(&parse_format::parse_format_string(format).unwrap())
+    };
+    let result = test_result();
+    assert_eq!(
+        fill(&result, "%X %05y|%-6w|%7c"),
+        "0x01e00003 -0005|300   |  XTerm"
+    );
+
+    // A missing pid is not mistaken for 0
+    let result = HacksawResult::new(1, xcb::Rectangle::new(0, 0, 1, 1), SelectionMode::Drag);
+    assert_eq!(fill(&result, "[%05p]"), "[     ]");
+}
+
+#[test]
+fn test_derived_tokens() {
+    let derived = parse_format::parse_format_string("%r %b %C %a").unwrap();
+    assert_eq!(
+        test_result().fill_format_string(&derived),
+        "310 195 160,95 60000"
+    );
+}
+
+#[test]
+fn test_round_result() {
+    let rounded = |mut result: HacksawResult, multiple| {
+        result.round_to(multiple);
+        format_geometry(result.rect)
+    };
+    assert_eq!(rounded(test_result(), 16), "304x208+10+-5");
+    assert_eq!(rounded(test_result(), 1), "300x200+10+-5");
+    assert_eq!(rounded(test_result(), 0), "300x200+10+-5");
+
+    // Rounded down rather than off the screen
+    let mut result = HacksawResult::new(
+        1,
+        xcb::Rectangle::new(1700, 1000, 217, 73),
+        SelectionMode::Window,
+    );
+    assert_eq!(rounded(result.clone(), 16), "224x80+1700+1000");
+    result.screen = Some(xcb::Rectangle::new(0, 0, 1920, 1080));
+    assert_eq!(rounded(result, 16), "208x80+1700+1000");
+}
+
+#[test]
+fn test_negative_geometry() {
+    let geometry = |x, y, screen| {
+        let mut result =
+            HacksawResult::new(1, xcb::Rectangle::new(x, y, 300, 200), SelectionMode::Drag);
+        result.screen = screen;
+        result.fill_format_string(&[FormatToken::NegativeGeometry])
+    };
+    let screen = Some(xcb::Rectangle::new(0, 0, 1920, 1080));
+
+    assert_eq!(geometry(10, -5, screen), "300x200-1610-885");
+    assert_eq!(geometry(1620, 880, screen), "300x200-0-0");
+    // Past the right and bottom edges
+    assert_eq!(geometry(1625, 890, screen), "300x200-0-0");
+    // Unknown without the screen
+    assert_eq!(geometry(10, -5, None), "");
+}
+
+#[test]
+fn test_skip_vanished() {
+    assert!(matches!(
+        skip_vanished::<()>(Err(HacksawError::WindowVanished)),
+        Ok(None)
+    ));
+    assert!(matches!(skip_vanished(Ok(Some(1))), Ok(Some(1))));
+    assert!(matches!(
+        skip_vanished::<()>(Err(HacksawError::Request(8))),
+        Err(HacksawError::Request(8))
+    ));
+}
+
+#[test]
+fn test_apply_extents() {
+    let rect = xcb::Rectangle::new(100, 100, 400, 300);
+    let tuple = |r: xcb::Rectangle| (r.x(), r.y(), r.width(), r.height());
+
+    assert_eq!(
+        tuple(apply_extents(rect, &[2, 2, 24, 2], true)),
+        (98, 76, 404, 326)
+    );
+    assert_eq!(
+        tuple(apply_extents(rect, &[10, 10, 5, 15], false)),
+        (110, 105, 380, 280)
+    );
+    // Shadows larger than the window leave nothing rather than wrapping
+    assert_eq!(
+        tuple(apply_extents(rect, &[300, 300, 0, 0], false)),
+        (400, 100, 0, 300)
+    );
+    // Malformed properties are ignored
+    assert_eq!(tuple(apply_extents(rect, &[1, 2], true)), tuple(rect));
+}
+
+#[test]
+fn test_lasso() {
+    let selection = xcb::Rectangle::new(100, 100, 200, 200);
+    let matches = |lasso: Lasso, x, y| lasso.matches(selection, xcb::Rectangle::new(x, y, 50, 50));
+
+    assert!(matches(Lasso::Intersecting, 80, 80));
+    assert!(matches(Lasso::Intersecting, 150, 150));
+    // Only touching the edge is not enough
+    assert!(!matches(Lasso::Intersecting, 50, 150));
+    assert!(!matches(Lasso::Intersecting, 300, 150));
+
+    assert!(!matches(Lasso::Contained, 80, 80));
+    assert!(matches(Lasso::Contained, 150, 150));
+    assert!(matches(Lasso::Contained, 250, 250));
 }
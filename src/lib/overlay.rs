@@ -0,0 +1,126 @@
+//! Parts shared by the small windows following the pointer: the label and
+//! the magnifier.
+
+use super::HacksawError;
+
+const FONT_NAME: &str = "fixed";
+/// Space between text and the edge of its box.
+pub const PADDING: u16 = 4;
+/// Distance between the pointer and a window following it.
+const CURSOR_OFFSET: i16 = 16;
+
+/// Which side of the pointer a window following it would rather be on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Corner {
+    BottomRight,
+    TopLeft,
+}
+
+/// Position a `width`x`height` window near `cursor`, towards `corner`
+/// unless that would leave `bounds`, in which case it flips to the other
+/// side of the pointer.
+pub fn place(
+    bounds: xcb::Rectangle,
+    cursor: xcb::Point,
+    width: u16,
+    height: u16,
+    corner: Corner,
+) -> xcb::Point {
+    let along = |cursor: i16, low: i16, length: u16, size: u16| {
+        let (cursor, low, size) = (i32::from(cursor), i32::from(low), i32::from(size));
+        let high = low + i32::from(length);
+        let after = cursor + i32::from(CURSOR_OFFSET);
+        let before = cursor - i32::from(CURSOR_OFFSET) - size;
+        let pos = match corner {
+            Corner::BottomRight if after + size > high => before,
+            Corner::BottomRight => after,
+            Corner::TopLeft if before < low => after,
+            Corner::TopLeft => before,
+        };
+        // The top left stays visible if it doesn't fit either way
+        pos.min(high - size).max(low) as i16
+    };
+
+    xcb::Point::new(
+        along(cursor.x(), bounds.x(), bounds.width(), width),
+        along(cursor.y(), bounds.y(), bounds.height(), height),
+    )
+}
+
+/// The font text is drawn in, open until it is handed to [`Font::create_gc`].
+pub struct Font {
+    id: xcb::Font,
+    pub ascent: i16,
+    pub descent: i16,
+    pub char_width: u16,
+}
+
+impl Font {
+    pub fn open(conn: &xcb::Connection) -> Result<Font, HacksawError> {
+        let id = conn.generate_id();
+        xcb::open_font_checked(conn, id, FONT_NAME).request_check()?;
+        let info = xcb::query_font(conn, id).get_reply()?;
+        Ok(Font {
+            id,
+            ascent: info.font_ascent(),
+            descent: info.font_descent(),
+            char_width: info.max_bounds().character_width() as u16,
+        })
+    }
+
+    /// Height of a line of text with padding above and below.
+    pub fn line_height(&self) -> u16 {
+        (self.ascent + self.descent) as u16 + 2 * PADDING
+    }
+
+    /// Create a graphics context drawing white on black in this font on
+    /// `window`, and close the font.
+    pub fn create_gc(
+        self,
+        conn: &xcb::Connection,
+        screen: &xcb::Screen,
+        window: xcb::Window,
+    ) -> xcb::Gcontext {
+        let gc = conn.generate_id();
+        xcb::create_gc(
+            conn,
+            gc,
+            window,
+            &[
+                (xcb::GC_FOREGROUND, screen.white_pixel()),
+                (xcb::GC_BACKGROUND, screen.black_pixel()),
+                (xcb::GC_FONT, self.id),
+                (xcb::GC_GRAPHICS_EXPOSURES, 0),
+            ],
+        );
+        xcb::close_font(conn, self.id);
+        gc
+    }
+}
+
+#[test]
+fn test_place() {
+    let bounds = xcb::Rectangle::new(0, 0, 1920, 1080);
+    let at = |x, y, corner| {
+        let pos = place(bounds, xcb::Point::new(x, y), 100, 120, corner);
+        (pos.x(), pos.y())
+    };
+
+    assert_eq!(at(500, 500, Corner::BottomRight), (516, 516));
+    assert_eq!(at(500, 500, Corner::TopLeft), (384, 364));
+    // Flipped to stay on screen near the edges
+    assert_eq!(at(1850, 500, Corner::BottomRight), (1734, 516));
+    assert_eq!(at(500, 1070, Corner::BottomRight), (516, 934));
+    assert_eq!(at(50, 500, Corner::TopLeft), (66, 364));
+    assert_eq!(at(500, 50, Corner::TopLeft), (384, 66));
+
+    // Never pushed past the top left either
+    let pos = place(
+        xcb::Rectangle::new(0, 0, 110, 30),
+        xcb::Point::new(50, 20),
+        100,
+        20,
+        Corner::BottomRight,
+    );
+    assert_eq!((pos.x(), pos.y()), (0, 0));
+}
@@ -2,37 +2,179 @@
 #[derive(Debug, PartialEq)]
 pub enum FormatToken {
     WindowId,
+    /// The window id in hex with a `0x` prefix, as `xdotool` and `wmctrl`
+    /// print it.
+    WindowIdHex,
     Geometry,
     Width,
     Height,
     X,
     Y,
+    /// `x + width`
+    Right,
+    /// `y + height`
+    Bottom,
+    Centre,
+    Area,
+    /// X geometry with offsets from the right and bottom of the screen.
+    NegativeGeometry,
+    MonitorName,
+    MonitorGeometry,
+    Class,
+    Instance,
+    Title,
+    Pid,
+    Desktop,
+    Windows,
+    Json,
     Literal(String),
+    /// Another token padded to a minimum width, as in `%05x` or `%-6w`.
+    Padded(Spec, Box<FormatToken>),
+}
+
+impl FormatToken {
+    /// Whether the token stands for a number, which can be zero-filled.
+    pub fn is_numeric(&self) -> bool {
+        match self {
+            FormatToken::WindowId
+            | FormatToken::WindowIdHex
+            | FormatToken::Width
+            | FormatToken::Height
+            | FormatToken::X
+            | FormatToken::Y
+            | FormatToken::Right
+            | FormatToken::Bottom
+            | FormatToken::Area
+            | FormatToken::Pid
+            | FormatToken::Desktop => true,
+            FormatToken::Padded(_, token) => token.is_numeric(),
+            _ => false,
+        }
+    }
+}
+
+/// printf-style modifiers between the `%` and the format character.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Spec {
+    /// `-`: pad on the right instead of the left.
+    pub left_align: bool,
+    /// `0`: pad numbers with zeros instead of spaces.
+    pub zero_fill: bool,
+    pub width: usize,
+}
+
+impl Spec {
+    /// Pad `value` to the width. Zeros go after a sign or `0x` prefix, and
+    /// are only used for numbers. An empty value, like a missing pid, is
+    /// padded with spaces so that it doesn't read as a 0.
+    pub fn apply(self, value: &str, numeric: bool) -> String {
+        let padding = self.width.saturating_sub(value.chars().count());
+        if self.left_align {
+            format!("{}{}", value, " ".repeat(padding))
+        } else if self.zero_fill && numeric && !value.is_empty() {
+            let digits = value.trim_start_matches('-').trim_start_matches("0x");
+            let (prefix, digits) = value.split_at(value.len() - digits.len());
+            format!("{}{}{}", prefix, "0".repeat(padding), digits)
+        } else {
+            format!("{}{}", " ".repeat(padding), value)
+        }
+    }
+}
+
+/// Split the flags and width off the start of a format. Fails with the rest
+/// of the input if the width is too large.
+fn parse_spec(input: &[u8]) -> Result<(Spec, &[u8]), &[u8]> {
+    let mut spec = Spec::default();
+    let mut rest = input;
+    while let Some((flag, flag_rest)) = rest.split_first() {
+        match flag {
+            b'-' => spec.left_align = true,
+            b'0' => spec.zero_fill = true,
+            _ => break,
+        }
+        rest = flag_rest;
+    }
+
+    let digits = rest.iter().take_while(|c| c.is_ascii_digit()).count();
+    let (width, rest) = rest.split_at(digits);
+    if !width.is_empty() {
+        spec.width = String::from_utf8_lossy(width)
+            .parse::<u16>()
+            .map_err(|_| rest)?
+            .into();
+    }
+    Ok((spec, rest))
 }
 
 // Get around structopt automatic Vec handling.
-pub(crate) type Format = Vec<FormatToken>;
+pub type Format = Vec<FormatToken>;
 
-pub(crate) fn parse_format_string(input: &str) -> Result<Format, String> {
+pub fn parse_format_string(input: &str) -> Result<Format, String> {
     let mut tokens = Vec::new();
     let mut input = input.as_bytes();
 
     loop {
         let (token, rest) = match input.split_first() {
-            Some((b'%', format_rest)) => match format_rest.split_first() {
-                Some((b'i', rest)) => (FormatToken::WindowId, rest),
-                Some((b'g', rest)) => (FormatToken::Geometry, rest),
-                Some((b'w', rest)) => (FormatToken::Width, rest),
-                Some((b'h', rest)) => (FormatToken::Height, rest),
-                Some((b'x', rest)) => (FormatToken::X, rest),
-                Some((b'y', rest)) => (FormatToken::Y, rest),
-                Some((b'%', rest)) => (FormatToken::Literal("%".to_owned()), rest),
-                Some((c, _)) => break Err(format!("Unknown format '%{}'", *c as char)),
-                None => break Err("Incorrectly terminated '%'".to_owned()),
-            },
+            Some((b'%', format_rest)) => {
+                let (spec, format_rest) = match parse_spec(format_rest) {
+                    Ok(parsed) => parsed,
+                    Err(rest) => {
+                        let modifiers = &input[1..input.len() - rest.len()];
+                        break Err(format!(
+                            "Width too large in '%{}'",
+                            String::from_utf8_lossy(modifiers)
+                        ));
+                    }
+                };
+                let modifiers = &input[1..input.len() - format_rest.len()];
+                let (token, rest) = match format_rest.split_first() {
+                    Some((b'i', rest)) => (FormatToken::WindowId, rest),
+                    Some((b'X', rest)) => (FormatToken::WindowIdHex, rest),
+                    Some((b'g', rest)) => (FormatToken::Geometry, rest),
+                    Some((b'w', rest)) => (FormatToken::Width, rest),
+                    Some((b'h', rest)) => (FormatToken::Height, rest),
+                    Some((b'x', rest)) => (FormatToken::X, rest),
+                    Some((b'y', rest)) => (FormatToken::Y, rest),
+                    Some((b'r', rest)) => (FormatToken::Right, rest),
+                    Some((b'b', rest)) => (FormatToken::Bottom, rest),
+                    Some((b'C', rest)) => (FormatToken::Centre, rest),
+                    Some((b'a', rest)) => (FormatToken::Area, rest),
+                    Some((b'G', rest)) => (FormatToken::NegativeGeometry, rest),
+                    Some((b'm', rest)) => (FormatToken::MonitorName, rest),
+                    Some((b'M', rest)) => (FormatToken::MonitorGeometry, rest),
+                    Some((b'c', rest)) => (FormatToken::Class, rest),
+                    Some((b'n', rest)) => (FormatToken::Instance, rest),
+                    Some((b't', rest)) => (FormatToken::Title, rest),
+                    Some((b'p', rest)) => (FormatToken::Pid, rest),
+                    Some((b'd', rest)) => (FormatToken::Desktop, rest),
+                    Some((b'W', rest)) => (FormatToken::Windows, rest),
+                    Some((b'j', rest)) => (FormatToken::Json, rest),
+                    Some((b'%', rest)) if modifiers.is_empty() => {
+                        (FormatToken::Literal("%".to_owned()), rest)
+                    }
+                    Some((&c, _)) => {
+                        break Err(format!(
+                            "Unknown format '%{}{}'",
+                            String::from_utf8_lossy(modifiers),
+                            c as char
+                        ))
+                    }
+                    None => {
+                        break Err(format!(
+                            "Incorrectly terminated '%{}'",
+                            String::from_utf8_lossy(modifiers)
+                        ))
+                    }
+                };
+                if spec.width == 0 {
+                    (token, rest)
+                } else {
+                    (FormatToken::Padded(spec, Box::new(token)), rest)
+                }
+            }
             Some((_, _)) => {
                 let next_perc = input.iter().position(|&c| c == b'%');
-                let (literal, rest) = input.split_at(next_perc.unwrap_or_else(|| input.len()));
+                let (literal, rest) = input.split_at(next_perc.unwrap_or(input.len()));
                 let literal = FormatToken::Literal(String::from_utf8_lossy(literal).into_owned());
                 (literal, rest)
             }
@@ -69,6 +211,90 @@ fn test_parse_format_string() {
 
     assert_eq!(parse_format_string("%g"), Ok(vec![FormatToken::Geometry]));
 
+    assert_eq!(
+        parse_format_string("%r%b%C%a%G"),
+        Ok(vec![
+            FormatToken::Right,
+            FormatToken::Bottom,
+            FormatToken::Centre,
+            FormatToken::Area,
+            FormatToken::NegativeGeometry,
+        ])
+    );
+
+    assert_eq!(
+        parse_format_string("%m:%M"),
+        Ok(vec![
+            FormatToken::MonitorName,
+            FormatToken::Literal(":".into()),
+            FormatToken::MonitorGeometry,
+        ])
+    );
+
+    assert_eq!(
+        parse_format_string("%c.%n %t (%p@%d)"),
+        Ok(vec![
+            FormatToken::Class,
+            FormatToken::Literal(".".into()),
+            FormatToken::Instance,
+            FormatToken::Literal(" ".into()),
+            FormatToken::Title,
+            FormatToken::Literal(" (".into()),
+            FormatToken::Pid,
+            FormatToken::Literal("@".into()),
+            FormatToken::Desktop,
+            FormatToken::Literal(")".into()),
+        ])
+    );
+
+    assert_eq!(parse_format_string("%W"), Ok(vec![FormatToken::Windows]));
+
+    assert_eq!(
+        parse_format_string("%05x%-6w%X%08X"),
+        Ok(vec![
+            FormatToken::Padded(
+                Spec {
+                    left_align: false,
+                    zero_fill: true,
+                    width: 5
+                },
+                Box::new(FormatToken::X)
+            ),
+            FormatToken::Padded(
+                Spec {
+                    left_align: true,
+                    zero_fill: false,
+                    width: 6
+                },
+                Box::new(FormatToken::Width)
+            ),
+            FormatToken::WindowIdHex,
+            FormatToken::Padded(
+                Spec {
+                    left_align: false,
+                    zero_fill: true,
+                    width: 8
+                },
+                Box::new(FormatToken::WindowIdHex)
+            ),
+        ])
+    );
+    // Flags without a width change nothing
+    assert_eq!(parse_format_string("%0w"), Ok(vec![FormatToken::Width]));
+    assert_eq!(
+        parse_format_string("%-5q"),
+        Err("Unknown format '%-5q'".into())
+    );
+    assert_eq!(
+        parse_format_string("%03"),
+        Err("Incorrectly terminated '%03'".into())
+    );
+    assert!(parse_format_string("%5%").is_err());
+    assert_eq!(
+        parse_format_string("%099999x"),
+        Err("Width too large in '%099999'".into())
+    );
+
     assert!(parse_format_string("%-").is_err());
     assert!(parse_format_string("%-").unwrap_err().contains("'%-'"));
 
@@ -79,3 +305,26 @@ fn test_parse_format_string() {
         Ok(vec![FormatToken::Literal("hello world".into())])
     );
 }
+
+#[test]
+fn test_spec() {
+    let spec = |left_align, zero_fill, width| Spec {
+        left_align,
+        zero_fill,
+        width,
+    };
+    assert_eq!(spec(false, false, 5).apply("42", true), "   42");
+    assert_eq!(spec(true, true, 5).apply("42", true), "42   ");
+    assert_eq!(spec(false, true, 5).apply("42", true), "00042");
+    assert_eq!(spec(false, true, 5).apply("-42", true), "-0042");
+    assert_eq!(
+        spec(false, true, 12).apply("0x01e00003", true),
+        "0x0001e00003"
+    );
+    assert_eq!(spec(false, true, 6).apply("XTerm", false), " XTerm");
+    // Absent values
+    assert_eq!(spec(false, true, 5).apply("", true), "     ");
+    assert_eq!(spec(true, true, 3).apply("", true), "   ");
+    assert_eq!(spec(false, false, 2).apply("élan", false), "élan");
+    assert_eq!(spec(true, false, 5).apply("é", false), "é    ");
+}
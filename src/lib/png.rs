@@ -0,0 +1,105 @@
+//! A minimal PNG encoder for 8-bit RGB images.
+
+use miniz_oxide::deflate::compress_to_vec_zlib;
+
+use std::io::{self, Write};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+/// Colour type for truecolour without alpha.
+const COLOUR_TYPE_RGB: u8 = 2;
+/// Deflate level, from 0 to 10. Higher levels are much slower for little
+/// gain on screenshots.
+const COMPRESSION_LEVEL: u8 = 6;
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 != 0 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+        }
+        *entry = c;
+    }
+
+    !data.iter().fold(!0, |crc, &byte| {
+        table[((crc ^ u32::from(byte)) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+fn write_chunk(out: &mut dyn Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    let mut crc_data = Vec::with_capacity(4 + data.len());
+    crc_data.extend_from_slice(kind);
+    crc_data.extend_from_slice(data);
+
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(&crc_data)?;
+    out.write_all(&crc32(&crc_data).to_be_bytes())
+}
+
+/// Write a `width`x`height` image with packed RGB `pixels` as a PNG file.
+pub fn write(out: &mut dyn Write, width: u16, height: u16, pixels: &[u8]) -> io::Result<()> {
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&u32::from(width).to_be_bytes());
+    header.extend_from_slice(&u32::from(height).to_be_bytes());
+    // Bit depth, colour type, compression, filter, interlace
+    header.extend_from_slice(&[8, COLOUR_TYPE_RGB, 0, 0, 0]);
+
+    let row = width as usize * 3;
+    let mut scanlines = Vec::with_capacity((row + 1) * height as usize);
+    for line in pixels.chunks(row.max(1)).take(height as usize) {
+        // No filter
+        scanlines.push(0);
+        scanlines.extend_from_slice(line);
+    }
+
+    out.write_all(&SIGNATURE)?;
+    write_chunk(out, b"IHDR", &header)?;
+    write_chunk(
+        out,
+        b"IDAT",
+        &compress_to_vec_zlib(&scanlines, COMPRESSION_LEVEL),
+    )?;
+    write_chunk(out, b"IEND", &[])
+}
+
+#[test]
+fn test_checksums() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+}
+
+#[test]
+fn test_write() {
+    use miniz_oxide::inflate::decompress_to_vec_zlib;
+
+    let (width, height) = (640, 480);
+    let pixels = (0..width * height)
+        .flat_map(|i| [(i % 7) as u8, 0x40, 0xFF])
+        .collect::<Vec<_>>();
+    let mut out = vec![];
+    write(&mut out, width as u16, height as u16, &pixels).unwrap();
+
+    assert_eq!(&out[..8], &SIGNATURE);
+    assert_eq!(&out[12..16], b"IHDR");
+    assert_eq!(&out[16..24], &[0, 0, 2, 0x80, 0, 0, 1, 0xE0]);
+    assert_eq!(&out[out.len() - 12..], b"\0\0\0\0IEND\xAE\x42\x60\x82");
+    // The repetitive image has to compress well below its raw size
+    assert!(out.len() < pixels.len() / 20);
+
+    let idat = 8 + 12 + 13;
+    let len = u32::from_be_bytes([out[idat], out[idat + 1], out[idat + 2], out[idat + 3]]);
+    assert_eq!(&out[idat + 4..idat + 8], b"IDAT");
+    let scanlines = decompress_to_vec_zlib(&out[idat + 8..idat + 8 + len as usize]).unwrap();
+    assert_eq!(scanlines.len(), (width * 3 + 1) * height);
+    for (line, row) in scanlines
+        .chunks(width * 3 + 1)
+        .zip(pixels.chunks(width * 3))
+    {
+        assert_eq!(line[0], 0);
+        assert_eq!(&line[1..], row);
+    }
+}
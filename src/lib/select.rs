@@ -0,0 +1,714 @@
+use super::image::PixelFormat;
+use super::label::Label;
+use super::magnifier::Magnifier;
+use super::selection::{
+    surround, Input, Key, Modifiers, Outcome, Selection, SelectionConfig, SelectionState,
+    SCROLL_DOWN, SCROLL_UP,
+};
+use super::HacksawError;
+use super::{
+    compositor_running, find_argb_visual, find_keycode, find_monitor, format_geometry, get_clients,
+    get_monitors, get_window_geom, get_window_info, get_window_path_at_point, get_windows,
+    get_windows_in, grab_keys, grab_pointer_set_cursor, premultiply, remove_frames, round_rect,
+    set_shape, set_title, ungrab_keys, Extents, HacksawResult, Lasso, SelectionMode, DOWN_KEYSYM,
+    ESC_KEYSYM, LEFT_KEYSYM, RETURN_KEYSYM, RIGHT_KEYSYM, UP_KEYSYM,
+};
+
+/// Longest time from pressing a button to pressing it again for the two
+/// to count as a double click.
+const DOUBLE_CLICK_MS: xcb::Timestamp = 400;
+/// Furthest the pointer may move between the presses of a double click, in
+/// either direction.
+const DOUBLE_CLICK_DISTANCE: i32 = 4;
+
+/// Settings for an interactive selection, see [`select`].
+///
+/// New settings may be added in minor releases, so build these from
+/// [`Options::default`] and set the fields you need:
+///
+/// ```no_run
+/// let mut options = hacksaw::Options::default();
+/// options.remove_decorations = 1;
+/// let result = hacksaw::select(&options);
+/// ```
+#[derive(Clone)]
+#[non_exhaustive]
+pub struct Options {
+    /// Show fighter pilot guide lines before a selection starts.
+    pub guides: bool,
+    pub guide_thickness: u16,
+    pub select_thickness: u16,
+    /// ARGB colour of the lines.
+    pub line_colour: u32,
+    /// ARGB colour of the lines while the selection is held back by
+    /// `max_size`, or is still under `min_size`.
+    pub limit_colour: u32,
+    /// Number of (nested) window manager frames to try and remove from a
+    /// picked window.
+    pub remove_decorations: u32,
+    /// Keep guides and selections on the monitor under the pointer, and
+    /// select the whole monitor when clicking on the desktop.
+    pub monitors: bool,
+    /// Keep selections at this width:height ratio.
+    pub aspect: Option<(u16, u16)>,
+    /// Show the size and position of the selection next to the pointer.
+    pub label: bool,
+    /// Shade everything but the selection with this ARGB colour. Only takes
+    /// effect when a compositor is running.
+    pub dim: Option<u32>,
+    /// Show a loupe with the pixels around the pointer zoomed in this many
+    /// times.
+    pub magnify: Option<u16>,
+    /// Start out with this region selected, ready to be adjusted or
+    /// confirmed with Enter.
+    pub initial_geometry: Option<xcb::Rectangle>,
+    /// After dragging, keep the selection on screen with handles to adjust
+    /// it, until it is confirmed with Enter or a double click.
+    pub edit: bool,
+    /// Snap the selection to window edges within this many pixels, unless
+    /// Ctrl is held. 0 disables snapping.
+    pub snap: u16,
+    /// Keep the width and height of dragged selections multiples of this
+    /// while dragging, along with any aspect ratio. 0 and 1 leave them
+    /// alone.
+    pub round_to: u16,
+    /// Round the width and height of the final selection, picked windows
+    /// and monitors included, to multiples of this, keeping it on the
+    /// screen. Unlike `round_to`, this ignores any aspect ratio. 0 and 1
+    /// leave them alone.
+    pub round_result: u16,
+    /// Throw away dragged selections smaller than this width and height,
+    /// so that the user can try again. Selections being edited are kept,
+    /// but can't be confirmed until they are large enough.
+    pub min_size: Option<(u16, u16)>,
+    /// Keep selections from growing beyond this width and height.
+    pub max_size: Option<(u16, u16)>,
+    /// List the windows in a dragged selection in
+    /// [`HacksawResult::windows`].
+    pub lasso: Option<Lasso>,
+    /// Pick windows from the window manager's `_NET_CLIENT_LIST_STACKING`
+    /// rather than the children of the root window, selecting these parts
+    /// of them. Falls back to the latter if the window manager does not
+    /// support it. Overrides `remove_decorations`.
+    pub ewmh: Option<Extents>,
+    /// Pick the deepest window under the pointer rather than a top-level
+    /// one. Shift and the scroll wheel walk up and down the hierarchy.
+    pub descend: bool,
+    /// Called with the name of an optional part, like the label or the
+    /// magnifier, and the reason it could not be shown. The selection goes
+    /// on without it. Ignores the error by default.
+    pub on_warning: fn(&str, &HacksawError),
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            guides: true,
+            guide_thickness: 1,
+            select_thickness: 1,
+            line_colour: 0xFF_7F_7F_7F,
+            limit_colour: 0xFF_FF_00_00,
+            remove_decorations: 0,
+            monitors: false,
+            aspect: None,
+            label: false,
+            dim: None,
+            magnify: None,
+            initial_geometry: None,
+            edit: false,
+            snap: 0,
+            round_to: 0,
+            round_result: 0,
+            min_size: None,
+            max_size: None,
+            lasso: None,
+            ewmh: None,
+            descend: false,
+            on_warning: |_, _| {},
+        }
+    }
+}
+
+/// Create a translucent window covering `rect`, or `None` if translucency is
+/// not available.
+fn create_dim_window(
+    conn: &xcb::Connection,
+    screen_num: i32,
+    screen: &xcb::Screen,
+    rect: xcb::Rectangle,
+    colour: u32,
+) -> Option<xcb::Window> {
+    if !compositor_running(conn, screen_num) {
+        return None;
+    }
+    let visual = find_argb_visual(screen)?;
+
+    let colormap = conn.generate_id();
+    xcb::create_colormap(
+        conn,
+        xcb::COLORMAP_ALLOC_NONE as u8,
+        colormap,
+        screen.root(),
+        visual,
+    );
+
+    let window = conn.generate_id();
+    let values = [
+        (xcb::CW_BACK_PIXEL, premultiply(colour)),
+        // Required when the depth differs from the parent's
+        (xcb::CW_BORDER_PIXEL, 0),
+        (xcb::CW_OVERRIDE_REDIRECT, 1u32),
+        (xcb::CW_COLORMAP, colormap),
+    ];
+    xcb::create_window(
+        conn,
+        32,
+        window,
+        screen.root(),
+        rect.x(),
+        rect.y(),
+        rect.width(),
+        rect.height(),
+        0,
+        xcb::WINDOW_CLASS_INPUT_OUTPUT as u16,
+        visual,
+        &values,
+    );
+    set_title(conn, window, "hacksaw");
+    set_shape(conn, window, &[]);
+    xcb::map_window(conn, window);
+
+    Some(window)
+}
+
+/// The window `level` steps up from the deepest one in `path`, stopping at
+/// the top.
+fn pick(path: &[HacksawResult], level: usize) -> Option<&HacksawResult> {
+    path.iter()
+        .rev()
+        .nth(level.min(path.len().saturating_sub(1)))
+}
+
+/// The level after scrolling `up` towards the top-level window in `path`, or
+/// back down towards the deepest one.
+fn walk_level(path: &[HacksawResult], level: usize, up: bool) -> usize {
+    if up {
+        (level + 1).min(path.len().saturating_sub(1))
+    } else {
+        level.saturating_sub(1)
+    }
+}
+
+/// Whether pressing `button` at `time` and `pt` makes a double click, after
+/// the last press and release.
+fn is_double_click(
+    last_press: Option<(u8, xcb::Timestamp, xcb::Point)>,
+    last_release: Option<(u8, xcb::Timestamp)>,
+    button: u8,
+    time: xcb::Timestamp,
+    pt: xcb::Point,
+) -> bool {
+    match (last_press, last_release) {
+        (Some((pressed, press_time, press_pt)), Some((released, release_time))) => {
+            let since_press = time.wrapping_sub(press_time);
+            let distance = |a: i16, b: i16| (i32::from(a) - i32::from(b)).abs();
+            pressed == button
+                && released == button
+                // Released in between the two presses
+                && release_time.wrapping_sub(press_time) <= since_press
+                && since_press <= DOUBLE_CLICK_MS
+                && distance(pt.x(), press_pt.x()) <= DOUBLE_CLICK_DISTANCE
+                && distance(pt.y(), press_pt.y()) <= DOUBLE_CLICK_DISTANCE
+        }
+        _ => false,
+    }
+}
+
+fn modifiers(state: u16) -> Modifiers {
+    Modifiers {
+        shift: state & xcb::MOD_MASK_SHIFT as u16 != 0,
+        control: state & xcb::MOD_MASK_CONTROL as u16 != 0,
+    }
+}
+
+/// Let the user select a region or window on the default display, blocking
+/// until they are done.
+pub fn select(options: &Options) -> Result<HacksawResult, HacksawError> {
+    Ok(run(options, false)?.remove(0))
+}
+
+/// Let the user select any number of regions or windows on the default
+/// display, keeping the earlier ones on screen, until they press Enter.
+pub fn select_multiple(options: &Options) -> Result<Vec<HacksawResult>, HacksawError> {
+    run(options, true)
+}
+
+fn run(options: &Options, multi: bool) -> Result<Vec<HacksawResult>, HacksawError> {
+    let (conn, screen_num) = xcb::Connection::connect(None)?;
+    let setup = conn.get_setup();
+    let screen = setup
+        .roots()
+        .nth(screen_num as usize)
+        .ok_or(xcb::ConnError::ClosedInvalidScreen)?;
+    let root = screen.root();
+
+    let window = conn.generate_id();
+
+    // TODO fix pointer-grab? bug where hacksaw hangs if mouse held down before run
+    grab_pointer_set_cursor(&conn, root)?;
+
+    let escape_keycode = find_keycode(&conn, ESC_KEYSYM)?.ok_or(HacksawError::NoEscapeKey)?;
+    let mut keys = vec![(escape_keycode, Key::Escape)];
+    for &(keysym, key) in &[
+        (RETURN_KEYSYM, Key::Return),
+        (LEFT_KEYSYM, Key::Left),
+        (RIGHT_KEYSYM, Key::Right),
+        (UP_KEYSYM, Key::Up),
+        (DOWN_KEYSYM, Key::Down),
+    ] {
+        if let Some(keycode) = find_keycode(&conn, keysym)? {
+            keys.push((keycode, key));
+        }
+    }
+    let keycodes = keys.iter().map(|&(keycode, _)| keycode).collect::<Vec<_>>();
+    grab_keys(&conn, root, &keycodes);
+
+    let screen_rect =
+        xcb::Rectangle::new(0, 0, screen.width_in_pixels(), screen.height_in_pixels());
+
+    // Before mapping anything, so that the loupe only shows what is below us
+    let magnifier = match (options.magnify, PixelFormat::of_root(&setup, &screen)) {
+        (Some(zoom), Some(format)) => Magnifier::new(&conn, &screen, format, zoom)
+            .map_err(|err| (options.on_warning)("magnifier", &err))
+            .ok(),
+        _ => None,
+    };
+
+    // Created first so that it stays below the lines
+    let dim_window = options
+        .dim
+        .and_then(|colour| create_dim_window(&conn, screen_num, &screen, screen_rect, colour));
+
+    // TODO event handling for expose/keypress
+    let values = [
+        // ?RGB. First 4 bytes appear to do nothing
+        (xcb::CW_BACK_PIXEL, options.line_colour),
+        (
+            xcb::CW_EVENT_MASK,
+            xcb::EVENT_MASK_EXPOSURE
+            | xcb::EVENT_MASK_KEY_PRESS // we'll need this later
+            | xcb::EVENT_MASK_STRUCTURE_NOTIFY
+            | xcb::EVENT_MASK_SUBSTRUCTURE_NOTIFY,
+        ),
+        (xcb::CW_OVERRIDE_REDIRECT, 1u32), // Don't be window managed
+    ];
+
+    xcb::create_window(
+        &conn,
+        xcb::COPY_FROM_PARENT as u8, // usually 32?
+        window,
+        root,
+        screen_rect.x(),
+        screen_rect.y(),
+        screen_rect.width(),
+        screen_rect.height(),
+        0,
+        xcb::WINDOW_CLASS_INPUT_OUTPUT as u16,
+        screen.root_visual(),
+        &values,
+    );
+
+    set_title(&conn, window, "hacksaw");
+
+    set_shape(&conn, window, &[xcb::Rectangle::new(0, 0, 0, 0)]);
+
+    xcb::map_window(&conn, window);
+
+    let mut label = if options.label {
+        Label::new(&conn, &screen)
+            .map_err(|err| (options.on_warning)("label", &err))
+            .ok()
+    } else {
+        None
+    };
+
+    let pointer = xcb::query_pointer(&conn, root).get_reply()?;
+    let pointer = xcb::Point::new(pointer.root_x(), pointer.root_y());
+    let monitors = get_monitors(&conn, root);
+    let overlays = [
+        Some(window),
+        dim_window,
+        label.as_ref().map(Label::window),
+        magnifier.as_ref().map(Magnifier::window),
+    ]
+    .iter()
+    .flatten()
+    .copied()
+    .collect::<Vec<_>>();
+    let remove_decorations = options.remove_decorations;
+    let whole_monitors = options.monitors;
+    // The window manager's list of clients when picking through EWMH, or
+    // `None` to go by the children of the root window instead
+    let clients = match options.ewmh {
+        Some(extents) => get_clients(&conn, root, extents, &overlays)?,
+        None => None,
+    };
+    // The windows to pick from and snap to, from bottom to top. Fetched once
+    // for the whole session, rather than on every motion of the pointer.
+    let toplevel = match &clients {
+        Some(clients) => clients.clone(),
+        None => get_windows(&conn, root, &overlays)?,
+    };
+    let window_at = |pt| match toplevel.iter().rev().find(|window| window.contains(pt)) {
+        Some(window) if clients.is_none() => {
+            remove_frames(&conn, window.clone(), remove_decorations).map(Some)
+        }
+        window => Ok(window.cloned()),
+    };
+    // The windows under `pt`, from the one picked at the top level down to
+    // the deepest child when descending
+    let path_at = |pt, ignore: &[xcb::Window]| match window_at(pt)? {
+        Some(top) if options.descend => get_window_path_at_point(&conn, root, top, pt, ignore),
+        Some(top) => Ok(vec![top]),
+        None => Ok(vec![]),
+    };
+
+    let windows = if options.snap > 0 {
+        toplevel.clone()
+    } else {
+        vec![]
+    };
+    let mut state = SelectionState::new(
+        SelectionConfig {
+            screen: screen_rect,
+            aspect: options.aspect,
+            monitors: if options.monitors {
+                monitors.iter().map(|monitor| monitor.rect).collect()
+            } else {
+                vec![]
+            },
+            select_thickness: options.select_thickness,
+            guide_thickness: options.guide_thickness,
+            guides: options.guides,
+            initial: options.initial_geometry,
+            edit: options.edit,
+            multi,
+            snap: options.snap,
+            round_to: options.round_to,
+            min_size: options.min_size,
+            max_size: options.max_size,
+            windows: windows.iter().map(|window| window.rect).collect(),
+        },
+        pointer,
+    );
+    let hover_rect = |path: &[HacksawResult], level, pt| match pick(path, level) {
+        Some(r) => Some(r.rect),
+        None if whole_monitors => find_monitor(&monitors, pt).map(|monitor| monitor.rect),
+        None => None,
+    };
+    // Windows may come and go while we hover, so errors here are not fatal
+    let mut path = path_at(pointer, &overlays).unwrap_or_default();
+    // How far up from the deepest window under the pointer to pick
+    let mut level = 0;
+    state.set_hover(hover_rect(&path, level, pointer));
+
+    let set_dim = |highlight: Option<xcb::Rectangle>| {
+        if let Some(dim_window) = dim_window {
+            let shape = highlight
+                .map(|highlight| surround(screen_rect, highlight))
+                .unwrap_or_default();
+            set_shape(&conn, dim_window, &shape);
+        }
+    };
+
+    let update_label = |label: &mut Option<Label>, state: &SelectionState| {
+        if let Some(label) = label {
+            match state.highlight() {
+                Some(selection) if state.in_selection() => {
+                    let bounds = find_monitor(&monitors, state.pointer())
+                        .map(|monitor| monitor.rect)
+                        .unwrap_or(screen_rect);
+                    // What the result will say, see `resolve`
+                    let selection = round_rect(selection, options.round_result, Some(screen_rect));
+                    label.show(&conn, format_geometry(selection), state.pointer(), bounds);
+                }
+                _ => label.hide(&conn),
+            }
+        }
+    };
+
+    let update_colour = |limited: &mut bool, state: &SelectionState| {
+        if state.at_limit() != *limited {
+            *limited = state.at_limit();
+            let colour = if *limited {
+                options.limit_colour
+            } else {
+                options.line_colour
+            };
+            xcb::change_window_attributes(&conn, window, &[(xcb::CW_BACK_PIXEL, colour)]);
+            xcb::clear_area(&conn, false, window, 0, 0, 0, 0);
+        }
+    };
+
+    let update_magnifier = |state: &SelectionState| {
+        if let Some(magnifier) = &magnifier {
+            let bounds = find_monitor(&monitors, state.pointer())
+                .map(|monitor| monitor.rect)
+                .unwrap_or(screen_rect);
+            magnifier.update(&conn, state.pointer(), bounds);
+        }
+    };
+
+    set_shape(&conn, window, &state.initial_shape());
+    set_dim(state.highlight());
+    update_magnifier(&state);
+    conn.flush();
+
+    let resolve = |selection, button, level, ignore: &[xcb::Window]| {
+        let mut result = match selection {
+            // Grab window under cursor
+            Selection::Point(pt) => match pick(&path_at(pt, ignore)?, level) {
+                Some(r) => r.clone(),
+                None => match find_monitor(&monitors, pt) {
+                    Some(monitor) if whole_monitors => {
+                        HacksawResult::new(root, monitor.rect, SelectionMode::Monitor)
+                    }
+                    _ => get_window_geom(&conn, screen.root())?,
+                },
+            },
+            Selection::Region(rect) => HacksawResult::new(root, rect, SelectionMode::Drag),
+        };
+        if result.mode == SelectionMode::Window {
+            result.info = Some(get_window_info(&conn, result.window));
+        }
+        if let (SelectionMode::Drag, Some(lasso)) = (result.mode, options.lasso) {
+            result.windows = match &clients {
+                Some(clients) => clients
+                    .iter()
+                    .filter(|client| lasso.matches(result.rect, client.rect))
+                    .cloned()
+                    .collect(),
+                None => {
+                    get_windows_in(&conn, root, result.rect, lasso, remove_decorations, ignore)?
+                }
+            };
+        }
+        result.button = button;
+
+        result.monitor = find_monitor(&monitors, result.centre()).cloned();
+        result.screen = Some(screen_rect);
+        result.round_to(options.round_result);
+
+        Ok::<_, HacksawError>(result)
+    };
+
+    let mut results = vec![];
+    let mut limited = false;
+    let mut last_press: Option<(u8, xcb::Timestamp, xcb::Point)> = None;
+    let mut last_release: Option<(u8, xcb::Timestamp)> = None;
+    let selection = loop {
+        let ev = conn.wait_for_event().ok_or(HacksawError::ConnectionLost)?;
+
+        let input = match ev.response_type() {
+            xcb::EXPOSE => {
+                if let Some(label) = &label {
+                    label.redraw(&conn);
+                }
+                update_magnifier(&state);
+                conn.flush();
+                continue;
+            }
+            xcb::BUTTON_PRESS => {
+                let button_press: &xcb::ButtonPressEvent = unsafe { xcb::cast_event(&ev) };
+                let button = button_press.detail();
+                let pt = xcb::Point::new(button_press.event_x(), button_press.event_y());
+                let walk = options.descend
+                    && !state.in_selection()
+                    && button_press.state() & xcb::MOD_MASK_SHIFT as u16 != 0;
+                if walk && (button == SCROLL_UP || button == SCROLL_DOWN) {
+                    level = walk_level(&path, level, button == SCROLL_UP);
+                    state.set_hover(hover_rect(&path, level, pt));
+                    set_shape(&conn, window, &state.current_shape());
+                    set_dim(state.highlight());
+                    conn.flush();
+                    continue;
+                }
+                let time = button_press.time();
+                if is_double_click(last_press, last_release, button, time, pt) {
+                    // A third click starts over rather than making another
+                    last_press = None;
+                    Input::DoubleClick(button, pt)
+                } else {
+                    last_press = Some((button, time, pt));
+                    Input::ButtonPress(button, pt)
+                }
+            }
+            xcb::BUTTON_RELEASE => {
+                let button_release: &xcb::ButtonReleaseEvent = unsafe { xcb::cast_event(&ev) };
+                last_release = Some((button_release.detail(), button_release.time()));
+                Input::ButtonRelease(button_release.detail())
+            }
+            xcb::MOTION_NOTIFY => {
+                let motion: &xcb::MotionNotifyEvent = unsafe { xcb::cast_event(&ev) };
+                let pt = xcb::Point::new(motion.event_x(), motion.event_y());
+                if !state.in_selection() {
+                    let new_path = path_at(pt, &overlays).unwrap_or_default();
+                    if new_path.last().map(|w| w.window) != path.last().map(|w| w.window) {
+                        level = 0;
+                    }
+                    path = new_path;
+                    state.set_hover(hover_rect(&path, level, pt));
+                }
+                Input::Motion(pt, modifiers(motion.state()))
+            }
+            xcb::KEY_PRESS => {
+                let key_press: &xcb::KeyPressEvent = unsafe { xcb::cast_event(&ev) };
+                let key = match keys.iter().find(|&&(code, _)| code == key_press.detail()) {
+                    Some(&(_, key)) => key,
+                    None => continue,
+                };
+                Input::Key(key, modifiers(key_press.state()))
+            }
+            _ => continue,
+        };
+
+        match state.handle(input) {
+            Outcome::Continue(update) => {
+                if let Some(shape) = update.shape {
+                    set_shape(&conn, window, &shape);
+                    set_dim(state.highlight());
+                    update_label(&mut label, &state);
+                    update_colour(&mut limited, &state);
+                    update_magnifier(&state);
+                }
+                if let Some(pt) = update.warp {
+                    xcb::warp_pointer(&conn, xcb::NONE, root, 0, 0, 0, 0, pt.x(), pt.y());
+                }
+                conn.flush();
+            }
+            Outcome::Done(selection) if multi => {
+                // Our windows are still up, so look past them
+                let result = resolve(selection, state.button(), level, &overlays)?;
+                set_shape(&conn, window, &state.keep(result.rect));
+                set_dim(state.highlight());
+                update_label(&mut label, &state);
+                update_colour(&mut limited, &state);
+                results.push(result);
+                conn.flush();
+            }
+            Outcome::Done(selection) => break Some(selection),
+            Outcome::Finished => break None,
+            Outcome::Cancelled(cancel) => return Err(cancel.into()),
+        }
+    };
+
+    xcb::ungrab_pointer(&conn, xcb::CURRENT_TIME);
+    ungrab_keys(&conn, root, &keycodes);
+    if let Some(dim_window) = dim_window {
+        xcb::destroy_window(&conn, dim_window);
+    }
+    if let Some(label) = label {
+        label.destroy(&conn);
+    }
+    if let Some(magnifier) = magnifier {
+        magnifier.destroy(&conn);
+    }
+    xcb::unmap_window(&conn, window);
+    xcb::destroy_window(&conn, window);
+    conn.flush();
+
+    loop {
+        let ev = conn.wait_for_event().ok_or(HacksawError::ConnectionLost)?;
+
+        match ev.response_type() {
+            xcb::UNMAP_NOTIFY => {
+                break;
+            }
+            xcb::DESTROY_NOTIFY => {
+                break;
+            }
+            _ => (),
+        }
+    }
+    std::thread::sleep(std::time::Duration::from_millis(40));
+
+    if let Some(selection) = selection {
+        results.push(resolve(selection, state.button(), level, &[])?);
+    }
+
+    Ok(results)
+}
+
+#[test]
+fn test_pick() {
+    let window =
+        |id| HacksawResult::new(id, xcb::Rectangle::new(0, 0, 10, 10), SelectionMode::Window);
+    let path = [window(1), window(2), window(3)];
+    let id = |level| pick(&path, level).map(|result| result.window);
+
+    // Levels count up from the deepest window, and stop at the top one
+    assert_eq!(id(0), Some(3));
+    assert_eq!(id(2), Some(1));
+    assert_eq!(id(5), Some(1));
+    assert!(pick(&[], 0).is_none());
+
+    let mut level = 0;
+    for &expected in &[2, 1, 1] {
+        level = walk_level(&path, level, true);
+        assert_eq!(id(level), Some(expected));
+    }
+    for &expected in &[2, 3, 3] {
+        level = walk_level(&path, level, false);
+        assert_eq!(id(level), Some(expected));
+    }
+    assert_eq!(walk_level(&[], 0, true), 0);
+}
+
+#[test]
+fn test_double_click() {
+    let pt = xcb::Point::new;
+    let double = |press, release, time, at| is_double_click(press, release, 1, time, at);
+
+    assert!(double(
+        Some((1, 1000, pt(10, 10))),
+        Some((1, 1100)),
+        1300,
+        pt(12, 9)
+    ));
+    // The first press was too long ago, like at the start of a long drag
+    assert!(!double(
+        Some((1, 1000, pt(10, 10))),
+        Some((1, 3000)),
+        3200,
+        pt(10, 10)
+    ));
+    // Too far from the first press
+    assert!(!double(
+        Some((1, 1000, pt(10, 10))),
+        Some((1, 1100)),
+        1300,
+        pt(20, 10)
+    ));
+    // Another button
+    assert!(!double(
+        Some((3, 1000, pt(10, 10))),
+        Some((3, 1100)),
+        1300,
+        pt(10, 10)
+    ));
+    // Not released in between
+    assert!(!double(
+        Some((1, 1000, pt(10, 10))),
+        Some((1, 900)),
+        1300,
+        pt(10, 10)
+    ));
+    assert!(!double(None, Some((1, 1100)), 1300, pt(10, 10)));
+    // Timestamps wrap around
+    assert!(double(
+        Some((1, u32::MAX - 100, pt(10, 10))),
+        Some((1, 50)),
+        150,
+        pt(10, 10)
+    ));
+}
@@ -0,0 +1,1695 @@
+//! The interactive part of hacksaw, kept free of any X calls so it can be
+//! driven (and tested) with plain input events.
+
+use std::iter::once;
+
+/// Pixels moved by an arrow key press, without and with Shift held.
+const KEY_STEP: i16 = 1;
+const KEY_STEP_SHIFT: i16 = 10;
+
+/// Size of the squares on the corners and edges of a pending selection.
+const HANDLE_SIZE: u16 = 7;
+
+const RIGHT_BUTTON: u8 = 3;
+pub(crate) const SCROLL_UP: u8 = 4;
+pub(crate) const SCROLL_DOWN: u8 = 5;
+
+fn is_scroll(button: u8) -> bool {
+    button == SCROLL_UP || button == SCROLL_DOWN
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Key {
+    Escape,
+    Return,
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub control: bool,
+}
+
+/// Pointer and keyboard input, with coordinates relative to the root window.
+#[derive(Clone, Copy)]
+pub enum Input {
+    ButtonPress(u8, xcb::Point),
+    ButtonRelease(u8),
+    Motion(xcb::Point, Modifiers),
+    Key(Key, Modifiers),
+    /// A button press following a release of the same button shortly before.
+    DoubleClick(u8, xcb::Point),
+}
+
+/// What should change on screen after an input has been handled.
+#[derive(Default)]
+pub struct Update {
+    /// New bounding shape of the overlay window.
+    pub shape: Option<Vec<xcb::Rectangle>>,
+    /// Where the pointer should be moved to.
+    pub warp: Option<xcb::Point>,
+}
+
+#[derive(Clone, Copy)]
+pub enum Selection {
+    /// A rectangle was dragged out.
+    Region(xcb::Rectangle),
+    /// A click without dragging, the window under this point should be picked.
+    Point(xcb::Point),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cancel {
+    Escape,
+    RightClick,
+}
+
+pub enum Outcome {
+    Continue(Update),
+    Done(Selection),
+    Cancelled(Cancel),
+    /// No more selections are wanted, see [`SelectionConfig::multi`].
+    Finished,
+}
+
+pub struct SelectionConfig {
+    pub screen: xcb::Rectangle,
+    /// Keep the selection at this width:height ratio. Without it, the ratio
+    /// is only kept while Shift is held, and is 1:1.
+    pub aspect: Option<(u16, u16)>,
+    /// Keep guides and selections within whichever of these contains the
+    /// pointer. Leave empty to use the whole screen.
+    pub monitors: Vec<xcb::Rectangle>,
+    pub select_thickness: u16,
+    pub guide_thickness: u16,
+    pub guides: bool,
+    /// Start out with this region already selected.
+    pub initial: Option<xcb::Rectangle>,
+    /// Keep dragged selections around for adjusting until they are
+    /// confirmed, instead of finishing when the button is released.
+    pub edit: bool,
+    /// Snap selection corners dragged within this many pixels of the edges of
+    /// `windows` onto them. 0 disables snapping.
+    pub snap: u16,
+    pub windows: Vec<xcb::Rectangle>,
+    /// Keep the width and height of dragged selections multiples of this.
+    /// 0 and 1 leave them alone.
+    pub round_to: u16,
+    /// Reject dragged selections smaller than this width and height.
+    pub min_size: Option<(u16, u16)>,
+    /// Keep selections from growing beyond this width and height.
+    pub max_size: Option<(u16, u16)>,
+    /// Make several selections, each of which should be handed back with
+    /// [`SelectionState::keep`]. Enter ends the session.
+    pub multi: bool,
+}
+
+/// Position of a handle along one axis of the selection.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Side {
+    Low,
+    Middle,
+    High,
+}
+
+const SIDES: [Side; 3] = [Side::Low, Side::Middle, Side::High];
+
+pub struct SelectionState {
+    config: SelectionConfig,
+    start: xcb::Point,
+    end: xcb::Point,
+    selection: xcb::Rectangle,
+    in_selection: bool,
+    ignore_next_release: bool,
+    hover: Option<xcb::Rectangle>,
+    button: Option<u8>,
+    /// Whether a button other than the scroll wheel is held down.
+    held: bool,
+    /// Whether Shift was held during the last pointer motion.
+    constrain: bool,
+    /// Whether Ctrl was held during the last pointer motion, which turns
+    /// snapping off.
+    snap_off: bool,
+    /// The area the current selection has to stay within.
+    bounds: xcb::Rectangle,
+    /// Where the pointer is. Unless the selection is pending, this is `end`.
+    cursor: xcb::Point,
+    /// A selection is shown without a button being held, because it was
+    /// given up front or in edit mode. It is moved by dragging from inside
+    /// of it, resized with its handles and only finishes on Enter or a
+    /// double click.
+    pending: bool,
+    /// Last pointer position while moving a pending selection.
+    grab: Option<xcb::Point>,
+    /// Whether `end` follows the pointer horizontally and vertically while
+    /// a handle is being dragged.
+    resize: Option<(bool, bool)>,
+    /// Earlier selections, kept on screen when making several.
+    kept: Vec<xcb::Rectangle>,
+    /// Whether the selection is being held back by `max_size`, or is still
+    /// under `min_size`.
+    limited: bool,
+}
+
+fn min_max(a: i16, b: i16) -> (i16, i16) {
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+fn build_selection(start: xcb::Point, end: xcb::Point) -> xcb::Rectangle {
+    let (left_x, right_x) = min_max(start.x(), end.x());
+    let (top_y, bottom_y) = min_max(start.y(), end.y());
+    xcb::Rectangle::new(
+        left_x,
+        top_y,
+        (right_x - left_x) as u16,
+        (bottom_y - top_y) as u16,
+    )
+}
+
+fn build_selection_rects(selection: xcb::Rectangle, width: u16) -> [xcb::Rectangle; 4] {
+    let (left_x, top_y) = (selection.x(), selection.y());
+    let right_x = left_x + selection.width() as i16;
+    let bottom_y = top_y + selection.height() as i16;
+    [
+        xcb::Rectangle::new(
+            left_x - width as i16,
+            top_y,
+            width,
+            selection.height() + width,
+        ),
+        xcb::Rectangle::new(
+            left_x - width as i16,
+            top_y - width as i16,
+            selection.width() + width,
+            width,
+        ),
+        xcb::Rectangle::new(
+            right_x,
+            top_y - width as i16,
+            width,
+            selection.height() + width,
+        ),
+        xcb::Rectangle::new(left_x, bottom_y, selection.width() + width, width),
+    ]
+}
+
+fn build_guides(screen: xcb::Rectangle, pt: xcb::Point, width: u16) -> [xcb::Rectangle; 2] {
+    [
+        xcb::Rectangle::new(
+            pt.x() - width as i16 / 2,
+            screen.y(),
+            width,
+            screen.height(),
+        ),
+        xcb::Rectangle::new(screen.x(), pt.y() - width as i16 / 2, screen.width(), width),
+    ]
+}
+
+/// Rectangles covering all of `outer` except for `hole`.
+pub fn surround(outer: xcb::Rectangle, hole: xcb::Rectangle) -> Vec<xcb::Rectangle> {
+    let outer_right = i32::from(outer.x()) + i32::from(outer.width());
+    let outer_bottom = i32::from(outer.y()) + i32::from(outer.height());
+    let left = i32::from(hole.x()).max(i32::from(outer.x()));
+    let top = i32::from(hole.y()).max(i32::from(outer.y()));
+    let right = (i32::from(hole.x()) + i32::from(hole.width())).min(outer_right);
+    let bottom = (i32::from(hole.y()) + i32::from(hole.height())).min(outer_bottom);
+
+    if left >= right || top >= bottom {
+        return vec![outer];
+    }
+
+    let rect = |x: i32, y: i32, right: i32, bottom: i32| {
+        xcb::Rectangle::new(x as i16, y as i16, (right - x) as u16, (bottom - y) as u16)
+    };
+    vec![
+        rect(outer.x().into(), outer.y().into(), outer_right, top),
+        rect(outer.x().into(), top, left, bottom),
+        rect(right, top, outer_right, bottom),
+        rect(outer.x().into(), bottom, outer_right, outer_bottom),
+    ]
+    .into_iter()
+    .filter(|r| r.width() > 0 && r.height() > 0)
+    .collect()
+}
+
+fn rect_contains(rect: xcb::Rectangle, pt: xcb::Point) -> bool {
+    rect.x() <= pt.x()
+        && rect.y() <= pt.y()
+        && pt.x() - rect.x() < rect.width() as i16
+        && pt.y() - rect.y() < rect.height() as i16
+}
+
+/// Clamp a selection corner to `bounds`, allowing it to sit on the far edges
+/// so that the full width and height can be selected.
+fn clamp_to_bounds(bounds: xcb::Rectangle, pt: xcb::Point) -> xcb::Point {
+    xcb::Point::new(
+        pt.x()
+            .max(bounds.x())
+            .min(bounds.x() + bounds.width() as i16),
+        pt.y()
+            .max(bounds.y())
+            .min(bounds.y() + bounds.height() as i16),
+    )
+}
+
+/// Move `end` so that the rectangle spanned from `start` has the given
+/// width:height `ratio`. The rectangle grows to contain `end` where possible,
+/// but shrinks to stay within `bounds` and no larger than `max`.
+fn constrain_aspect(
+    start: xcb::Point,
+    end: xcb::Point,
+    ratio: (u16, u16),
+    max: (u16, u16),
+    bounds: xcb::Rectangle,
+) -> xcb::Point {
+    let (ratio_w, ratio_h) = (i32::from(ratio.0.max(1)), i32::from(ratio.1.max(1)));
+    let (dx, dy) = (
+        i32::from(end.x()) - i32::from(start.x()),
+        i32::from(end.y()) - i32::from(start.y()),
+    );
+    let (mut width, mut height) = (dx.abs(), dy.abs());
+
+    if width * ratio_h >= height * ratio_w {
+        height = (width * ratio_h + ratio_w / 2) / ratio_w;
+    } else {
+        width = (height * ratio_w + ratio_h / 2) / ratio_h;
+    }
+
+    let max_width = if dx < 0 {
+        i32::from(start.x()) - i32::from(bounds.x())
+    } else {
+        i32::from(bounds.x()) + i32::from(bounds.width()) - i32::from(start.x())
+    };
+    let max_height = if dy < 0 {
+        i32::from(start.y()) - i32::from(bounds.y())
+    } else {
+        i32::from(bounds.y()) + i32::from(bounds.height()) - i32::from(start.y())
+    };
+    let (max_width, max_height) = (
+        max_width.min(i32::from(max.0)),
+        max_height.min(i32::from(max.1)),
+    );
+    if width > max_width {
+        width = max_width;
+        height = width * ratio_h / ratio_w;
+    }
+    if height > max_height {
+        height = max_height;
+        width = height * ratio_w / ratio_h;
+    }
+
+    // A flat drag still grows the other axis, downwards or to the right
+    let direction = |delta: i32| if delta < 0 { -1 } else { 1 };
+    xcb::Point::new(
+        (i32::from(start.x()) + width * direction(dx)) as i16,
+        (i32::from(start.y()) + height * direction(dy)) as i16,
+    )
+}
+
+/// Round a non-zero `length` to the nearest multiple of `multiple`, halfway
+/// rounding up, but not beyond `max`.
+pub(crate) fn round_length(length: i32, multiple: u16, max: i32) -> i32 {
+    let multiple = i32::from(multiple);
+    if length == 0 || multiple <= 1 {
+        return length;
+    }
+    let rounded = ((length + multiple / 2) / multiple).max(1) * multiple;
+    if rounded <= max {
+        rounded
+    } else if max >= multiple {
+        max / multiple * multiple
+    } else {
+        length.min(max)
+    }
+}
+
+/// Move `end` so that the rectangle spanned from `start` has a width and
+/// height that are multiples of `multiple`, within `bounds`.
+fn round_size(
+    start: xcb::Point,
+    end: xcb::Point,
+    multiple: u16,
+    bounds: xcb::Rectangle,
+) -> xcb::Point {
+    let round = |start: i16, end: i16, low: i16, length: u16| {
+        let (start, delta) = (i32::from(start), i32::from(end) - i32::from(start));
+        let max = if delta < 0 {
+            start - i32::from(low)
+        } else {
+            i32::from(low) + i32::from(length) - start
+        };
+        (start + round_length(delta.abs(), multiple, max) * delta.signum()) as i16
+    };
+    xcb::Point::new(
+        round(start.x(), end.x(), bounds.x(), bounds.width()),
+        round(start.y(), end.y(), bounds.y(), bounds.height()),
+    )
+}
+
+fn gcd(a: i32, b: i32) -> i32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Move `end` so that the rectangle spanned from `start`, already at the
+/// width:height `ratio`, keeps it exactly while its width and height become
+/// multiples of `multiple`. Falls back to [`round_size`], giving up on the
+/// ratio, if no such rectangle fits within `bounds` and `max`.
+fn round_aspect(
+    start: xcb::Point,
+    end: xcb::Point,
+    ratio: (u16, u16),
+    multiple: u16,
+    max: (u16, u16),
+    bounds: xcb::Rectangle,
+) -> xcb::Point {
+    let (dx, dy) = (
+        i32::from(end.x()) - i32::from(start.x()),
+        i32::from(end.y()) - i32::from(start.y()),
+    );
+    if multiple <= 1 || (dx == 0 && dy == 0) {
+        return end;
+    }
+
+    let (ratio_w, ratio_h) = (i32::from(ratio.0.max(1)), i32::from(ratio.1.max(1)));
+    let divisor = gcd(ratio_w, ratio_h);
+    let (ratio_w, ratio_h) = (ratio_w / divisor, ratio_h / divisor);
+    // Smallest scale of the ratio at which both sides are multiples
+    let multiple = i32::from(multiple);
+    let (scale_w, scale_h) = (
+        multiple / gcd(multiple, ratio_w),
+        multiple / gcd(multiple, ratio_h),
+    );
+    let scale = scale_w / gcd(scale_w, scale_h) * scale_h;
+    let (step_w, step_h) = (ratio_w * scale, ratio_h * scale);
+
+    let max_width = if dx < 0 {
+        i32::from(start.x()) - i32::from(bounds.x())
+    } else {
+        i32::from(bounds.x()) + i32::from(bounds.width()) - i32::from(start.x())
+    };
+    let max_height = if dy < 0 {
+        i32::from(start.y()) - i32::from(bounds.y())
+    } else {
+        i32::from(bounds.y()) + i32::from(bounds.height()) - i32::from(start.y())
+    };
+    let (max_width, max_height) = (
+        max_width.min(i32::from(max.0)),
+        max_height.min(i32::from(max.1)),
+    );
+    let steps = ((dx.abs() + step_w / 2) / step_w)
+        .max(1)
+        .min(max_width / step_w)
+        .min(max_height / step_h);
+    if steps == 0 {
+        return round_size(start, end, multiple as u16, bounds);
+    }
+
+    let direction = |delta: i32| if delta < 0 { -1 } else { 1 };
+    xcb::Point::new(
+        (i32::from(start.x()) + steps * step_w * direction(dx)) as i16,
+        (i32::from(start.y()) + steps * step_h * direction(dy)) as i16,
+    )
+}
+
+/// Move `end` towards `start` so that the rectangle spanned between them is
+/// no larger than `max`.
+fn limit_size(start: xcb::Point, end: xcb::Point, max: (u16, u16)) -> xcb::Point {
+    let limit = |start: i16, end: i16, max: u16| {
+        let (start, delta) = (i32::from(start), i32::from(end) - i32::from(start));
+        (start + delta.abs().min(i32::from(max)) * delta.signum()) as i16
+    };
+    xcb::Point::new(
+        limit(start.x(), end.x(), max.0),
+        limit(start.y(), end.y(), max.1),
+    )
+}
+
+fn clamp_to_screen(screen: xcb::Rectangle, pt: xcb::Point) -> xcb::Point {
+    xcb::Point::new(
+        pt.x()
+            .max(screen.x())
+            .min(screen.x() + screen.width() as i16 - 1),
+        pt.y()
+            .max(screen.y())
+            .min(screen.y() + screen.height() as i16 - 1),
+    )
+}
+
+/// Coordinate of `side` on an axis spanning `start` to `start + length`.
+fn side_position(start: i16, length: u16, side: Side) -> i16 {
+    match side {
+        Side::Low => start,
+        Side::Middle => start + (length / 2) as i16,
+        Side::High => start + length as i16,
+    }
+}
+
+/// Squares on the corners and edge midpoints of `selection`, with the side
+/// of the selection each one is on.
+fn build_handles(selection: xcb::Rectangle) -> Vec<((Side, Side), xcb::Rectangle)> {
+    let mut handles = vec![];
+    for &x_side in &SIDES {
+        for &y_side in &SIDES {
+            if (x_side, y_side) == (Side::Middle, Side::Middle) {
+                continue;
+            }
+            let x = side_position(selection.x(), selection.width(), x_side);
+            let y = side_position(selection.y(), selection.height(), y_side);
+            let rect = xcb::Rectangle::new(
+                x - (HANDLE_SIZE / 2) as i16,
+                y - (HANDLE_SIZE / 2) as i16,
+                HANDLE_SIZE,
+                HANDLE_SIZE,
+            );
+            handles.push(((x_side, y_side), rect));
+        }
+    }
+    // Corners win over edges when a small selection makes them overlap
+    handles.sort_by_key(|&((x_side, y_side), _)| {
+        (x_side == Side::Middle || y_side == Side::Middle) as u8
+    });
+    handles
+}
+
+/// Move `value` onto the closest of `edges` that is at most `distance` away.
+fn snap_to(value: i16, edges: impl Iterator<Item = i16>, distance: u16) -> i16 {
+    let offset = |edge: i16| (i32::from(edge) - i32::from(value)).abs();
+    edges
+        .filter(|&edge| offset(edge) <= i32::from(distance))
+        .min_by_key(|&edge| offset(edge))
+        .unwrap_or(value)
+}
+
+/// Whether `low..=high` comes within `distance` of `start..=start + length`.
+fn overlaps(low: i16, high: i16, start: i16, length: u16, distance: u16) -> bool {
+    i32::from(low) <= i32::from(start) + i32::from(length) + i32::from(distance)
+        && i32::from(high) >= i32::from(start) - i32::from(distance)
+}
+
+impl SelectionState {
+    pub fn new(config: SelectionConfig, pointer: xcb::Point) -> SelectionState {
+        let mut state = SelectionState {
+            bounds: config.screen,
+            config,
+            start: xcb::Point::new(0, 0),
+            end: pointer,
+            selection: xcb::Rectangle::new(pointer.x(), pointer.y(), 0, 0),
+            in_selection: false,
+            ignore_next_release: false,
+            hover: None,
+            button: None,
+            held: false,
+            constrain: false,
+            snap_off: false,
+            cursor: pointer,
+            pending: false,
+            grab: None,
+            resize: None,
+            kept: vec![],
+            limited: false,
+        };
+
+        if let Some(initial) = state.config.initial {
+            let start = xcb::Point::new(initial.x(), initial.y());
+            state.bounds = state.monitor_at(start);
+            state.start = clamp_to_bounds(state.bounds, start);
+            state.end = clamp_to_bounds(
+                state.bounds,
+                xcb::Point::new(
+                    initial.x().saturating_add(initial.width() as i16),
+                    initial.y().saturating_add(initial.height() as i16),
+                ),
+            );
+            state.in_selection = true;
+            state.pending = true;
+            state.update_selection();
+        }
+        state
+    }
+
+    pub fn in_selection(&self) -> bool {
+        self.in_selection
+    }
+
+    /// Where the pointer is, as far as the selection is concerned.
+    pub fn pointer(&self) -> xcb::Point {
+        self.cursor
+    }
+
+    /// The region currently of interest: the selection while one is being
+    /// made, or else the outlined window.
+    pub fn highlight(&self) -> Option<xcb::Rectangle> {
+        if self.in_selection {
+            Some(self.selection)
+        } else {
+            self.hover
+        }
+    }
+
+    /// The mouse button the current selection was started with.
+    pub fn button(&self) -> Option<u8> {
+        self.button
+    }
+
+    /// Outline `window` while no selection is being made, to show what a
+    /// click would pick.
+    pub fn set_hover(&mut self, window: Option<xcb::Rectangle>) {
+        self.hover = window;
+    }
+
+    /// Shape of the overlay before any input has been received.
+    pub fn initial_shape(&self) -> Vec<xcb::Rectangle> {
+        self.current_shape()
+    }
+
+    /// The monitor containing `pt`, or the whole screen.
+    fn monitor_at(&self, pt: xcb::Point) -> xcb::Rectangle {
+        self.config
+            .monitors
+            .iter()
+            .copied()
+            .find(|&monitor| rect_contains(monitor, pt))
+            .unwrap_or(self.config.screen)
+    }
+
+    fn idle_shape(&self) -> Vec<xcb::Rectangle> {
+        let mut shape = vec![];
+        if self.config.guides {
+            shape.extend_from_slice(&build_guides(
+                self.monitor_at(self.end),
+                self.end,
+                self.config.guide_thickness,
+            ));
+        }
+        if let Some(hover) = self.hover {
+            shape.extend_from_slice(&build_selection_rects(hover, self.config.select_thickness));
+        }
+        shape
+    }
+
+    /// Shape of the overlay in the current state, e.g. after the hover
+    /// outline changed.
+    pub fn current_shape(&self) -> Vec<xcb::Rectangle> {
+        let mut shape = if self.in_selection {
+            let mut shape =
+                build_selection_rects(self.selection, self.config.select_thickness).to_vec();
+            if self.pending {
+                shape.extend(build_handles(self.selection).iter().map(|&(_, rect)| rect));
+            }
+            shape
+        } else {
+            self.idle_shape()
+        };
+        for &kept in &self.kept {
+            shape.extend_from_slice(&build_selection_rects(kept, self.config.select_thickness));
+        }
+        shape
+    }
+
+    /// Whether the selection has hit `max_size`, or is still under
+    /// `min_size`.
+    pub fn at_limit(&self) -> bool {
+        self.in_selection && self.limited
+    }
+
+    /// Keep showing `rect`, the result of the selection that was just done,
+    /// and start over with the next one. Returns the new overlay shape.
+    pub fn keep(&mut self, rect: xcb::Rectangle) -> Vec<xcb::Rectangle> {
+        self.kept.push(rect);
+        self.reset();
+        self.current_shape()
+    }
+
+    /// Drop the current selection, if any. The release of a button still
+    /// held for it must not start anything new.
+    fn reset(&mut self) {
+        self.ignore_next_release = self.held;
+        self.in_selection = false;
+        self.pending = false;
+        self.grab = None;
+        self.resize = None;
+        self.button = None;
+        self.end = self.cursor;
+        self.update_selection();
+    }
+
+    fn too_small(&self, selection: xcb::Rectangle) -> bool {
+        match self.config.min_size {
+            Some((width, height)) => selection.width() < width || selection.height() < height,
+            None => false,
+        }
+    }
+
+    /// The ratio the selection currently has to keep, if any.
+    fn aspect(&self) -> Option<(u16, u16)> {
+        match self.config.aspect {
+            Some(ratio) => Some(ratio),
+            None if self.constrain => Some((1, 1)),
+            None => None,
+        }
+    }
+
+    fn update_selection(&mut self) {
+        // only save the width and height if we are selecting a rectangle,
+        // since we then use these (non-zero width/height) to determine if a
+        // selection was made.
+        self.selection = if self.in_selection {
+            let (start, end) = (self.start, clamp_to_bounds(self.bounds, self.end));
+            let (multiple, bounds) = (self.config.round_to, self.bounds);
+            // With an aspect ratio, both sides shrink together to fit `max`
+            let fit = |max: (u16, u16)| {
+                let end = match self.aspect() {
+                    Some(ratio) => {
+                        let end = constrain_aspect(start, end, ratio, max, bounds);
+                        round_aspect(start, end, ratio, multiple, max, bounds)
+                    }
+                    None => round_size(start, end, multiple, bounds),
+                };
+                limit_size(start, end, max)
+            };
+
+            let unlimited = fit((u16::MAX, u16::MAX));
+            let end = match self.config.max_size {
+                Some((max_width, max_height)) => {
+                    // Rounded down, so as not to undo the rounding
+                    let max = |length: u16| match multiple {
+                        multiple if multiple > 1 && length >= multiple => {
+                            length / multiple * multiple
+                        }
+                        _ => length,
+                    };
+                    fit((max(max_width), max(max_height)))
+                }
+                None => unlimited,
+            };
+            self.limited = (end.x(), end.y()) != (unlimited.x(), unlimited.y());
+
+            let selection = build_selection(self.start, end);
+            if selection.width() > 0 || selection.height() > 0 {
+                self.limited |= self.too_small(selection);
+            }
+            selection
+        } else {
+            xcb::Rectangle::new(self.end.x(), self.end.y(), 0, 0)
+        };
+    }
+
+    /// Snap `pt` onto the edges of nearby windows, for a selection spanning
+    /// from `from` to it. Only edges alongside the selection are considered.
+    fn snap(&self, from: xcb::Point, pt: xcb::Point) -> xcb::Point {
+        let distance = self.config.snap;
+        if distance == 0 || self.snap_off {
+            return pt;
+        }
+        let (left, right) = min_max(from.x(), pt.x());
+        let (top, bottom) = min_max(from.y(), pt.y());
+        let windows = &self.config.windows;
+
+        let x_edges = windows
+            .iter()
+            .filter(|w| overlaps(top, bottom, w.y(), w.height(), distance))
+            .flat_map(|w| once(w.x()).chain(once(w.x() + w.width() as i16)));
+        let y_edges = windows
+            .iter()
+            .filter(|w| overlaps(left, right, w.x(), w.width(), distance))
+            .flat_map(|w| once(w.y()).chain(once(w.y() + w.height() as i16)));
+
+        xcb::Point::new(
+            snap_to(pt.x(), x_edges, distance),
+            snap_to(pt.y(), y_edges, distance),
+        )
+    }
+
+    fn finish(&mut self) -> Outcome {
+        if self.selection.width() == 0 && self.selection.height() == 0 {
+            // The pointer rather than `start`, which may have been snapped
+            // off the window that was clicked
+            Outcome::Done(Selection::Point(self.cursor))
+        } else if self.too_small(self.selection) {
+            // A first drag this small was most likely an accident, let the
+            // user try again. One being edited stays, flagged as too small.
+            if !self.pending {
+                self.reset();
+            }
+            Outcome::Continue(Update {
+                shape: Some(self.current_shape()),
+                ..Default::default()
+            })
+        } else {
+            Outcome::Done(Selection::Region(self.selection))
+        }
+    }
+
+    /// Move a pending selection along with the pointer, keeping it within
+    /// its bounds.
+    fn drag_pending(&mut self, last: xcb::Point, pt: xcb::Point) {
+        let (left, top) = (self.selection.x(), self.selection.y());
+        let min_x = self.bounds.x() - left;
+        let min_y = self.bounds.y() - top;
+        let max_x =
+            self.bounds.x() + self.bounds.width() as i16 - (left + self.selection.width() as i16);
+        let max_y =
+            self.bounds.y() + self.bounds.height() as i16 - (top + self.selection.height() as i16);
+        let dx = (pt.x() - last.x()).max(min_x).min(max_x);
+        let dy = (pt.y() - last.y()).max(min_y).min(max_y);
+
+        self.start = xcb::Point::new(self.start.x() + dx, self.start.y() + dy);
+        self.end = xcb::Point::new(self.end.x() + dx, self.end.y() + dy);
+        self.update_selection();
+    }
+
+    /// Start resizing a pending selection from the handle at `side`, so
+    /// that `end` is the corner or edge being dragged.
+    fn start_resize(&mut self, (x_side, y_side): (Side, Side)) {
+        let (left, top) = (self.selection.x(), self.selection.y());
+        let right = left + self.selection.width() as i16;
+        let bottom = top + self.selection.height() as i16;
+        let (start_x, end_x) = if x_side == Side::Low {
+            (right, left)
+        } else {
+            (left, right)
+        };
+        let (start_y, end_y) = if y_side == Side::Low {
+            (bottom, top)
+        } else {
+            (top, bottom)
+        };
+
+        self.start = xcb::Point::new(start_x, start_y);
+        self.end = xcb::Point::new(end_x, end_y);
+        self.resize = Some((x_side != Side::Middle, y_side != Side::Middle));
+    }
+
+    pub fn handle(&mut self, input: Input) -> Outcome {
+        match input {
+            Input::ButtonPress(button, _) | Input::DoubleClick(button, _) if !is_scroll(button) => {
+                self.held = true
+            }
+            Input::ButtonRelease(button) if !is_scroll(button) => self.held = false,
+            _ => (),
+        }
+
+        match input {
+            Input::ButtonPress(RIGHT_BUTTON, _) => Outcome::Cancelled(Cancel::RightClick),
+            Input::DoubleClick(button, _) if self.pending && !is_scroll(button) => self.finish(),
+            Input::DoubleClick(button, pt) => self.handle(Input::ButtonPress(button, pt)),
+            Input::ButtonPress(button, pt) if self.pending && !is_scroll(button) => {
+                let handle = build_handles(self.selection)
+                    .into_iter()
+                    .find(|&(_, rect)| rect_contains(rect, pt));
+                if let Some((side, _)) = handle {
+                    self.start_resize(side);
+                } else if rect_contains(self.selection, pt) {
+                    self.grab = Some(pt);
+                } else {
+                    self.pending = false;
+                    return self.handle(input);
+                }
+                self.button = Some(button);
+                Outcome::Continue(Update::default())
+            }
+            Input::ButtonPress(button, pt) => {
+                self.pending = false;
+                self.cursor = pt;
+                self.start = self.snap(pt, pt);
+                self.bounds = self.monitor_at(pt);
+                self.in_selection = !is_scroll(button);
+                self.ignore_next_release = is_scroll(button);
+                self.button = if is_scroll(button) {
+                    None
+                } else {
+                    Some(button)
+                };
+
+                Outcome::Continue(Update {
+                    shape: Some(vec![]),
+                    ..Default::default()
+                })
+            }
+            Input::ButtonRelease(button) if is_scroll(button) => {
+                // Scroll wheel up/down release
+                Outcome::Continue(Update::default())
+            }
+            Input::ButtonRelease(_) if self.grab.is_some() || self.resize.is_some() => {
+                self.grab = None;
+                self.resize = None;
+                Outcome::Continue(Update::default())
+            }
+            Input::ButtonRelease(_) if self.ignore_next_release => {
+                self.ignore_next_release = false;
+                Outcome::Continue(Update::default())
+            }
+            Input::ButtonRelease(_)
+                if self.config.edit
+                    && self.selection.width() > 0
+                    && self.selection.height() > 0
+                    && !self.too_small(self.selection) =>
+            {
+                // Keep the selection around for adjusting
+                self.pending = true;
+                Outcome::Continue(Update {
+                    shape: Some(self.current_shape()),
+                    ..Default::default()
+                })
+            }
+            // Move on after mouse released
+            Input::ButtonRelease(_) => self.finish(),
+            Input::Motion(pt, modifiers) if self.pending => {
+                self.cursor = pt;
+                if let Some(last) = self.grab {
+                    self.drag_pending(last, pt);
+                    self.grab = Some(pt);
+                } else if let Some((follow_x, follow_y)) = self.resize {
+                    self.constrain = modifiers.shift;
+                    self.snap_off = modifiers.control;
+                    let snapped = self.snap(self.start, pt);
+                    self.end = xcb::Point::new(
+                        if follow_x { snapped.x() } else { self.end.x() },
+                        if follow_y { snapped.y() } else { self.end.y() },
+                    );
+                    self.update_selection();
+                }
+                Outcome::Continue(Update {
+                    shape: Some(self.current_shape()),
+                    ..Default::default()
+                })
+            }
+            Input::Motion(pt, modifiers) => {
+                self.cursor = pt;
+                self.constrain = modifiers.shift;
+                self.snap_off = modifiers.control;
+                self.end = if self.in_selection {
+                    self.snap(self.start, pt)
+                } else {
+                    pt
+                };
+                self.update_selection();
+
+                Outcome::Continue(Update {
+                    shape: Some(self.current_shape()),
+                    ..Default::default()
+                })
+            }
+            Input::Key(Key::Escape, _) => Outcome::Cancelled(Cancel::Escape),
+            Input::Key(Key::Return, _) if self.config.multi && !self.in_selection => {
+                Outcome::Finished
+            }
+            Input::Key(Key::Return, _) => {
+                if !self.in_selection {
+                    // Act like a click at the current pointer position
+                    self.start = self.end;
+                    self.cursor = self.end;
+                    self.update_selection();
+                }
+                self.finish()
+            }
+            Input::Key(key, modifiers) => {
+                let step = if modifiers.shift {
+                    KEY_STEP_SHIFT
+                } else {
+                    KEY_STEP
+                };
+                let (dx, dy) = match key {
+                    Key::Left => (-step, 0),
+                    Key::Right => (step, 0),
+                    Key::Up => (0, -step),
+                    Key::Down => (0, step),
+                    Key::Escape | Key::Return => unreachable!(),
+                };
+
+                let moved = if !self.in_selection {
+                    clamp_to_screen(
+                        self.config.screen,
+                        xcb::Point::new(self.end.x() + dx, self.end.y() + dy),
+                    )
+                } else if modifiers.control {
+                    // Move the whole rectangle rather than just the active
+                    // corner, as far as it fits
+                    let bounds = self.bounds;
+                    let limit = |delta: i16, a: i16, b: i16, low: i16, length: u16| {
+                        let high = low + length as i16;
+                        delta.max(low - a.min(b)).min(high - a.max(b))
+                    };
+                    let dx = limit(dx, self.start.x(), self.end.x(), bounds.x(), bounds.width());
+                    let dy = limit(
+                        dy,
+                        self.start.y(),
+                        self.end.y(),
+                        bounds.y(),
+                        bounds.height(),
+                    );
+                    self.start = xcb::Point::new(self.start.x() + dx, self.start.y() + dy);
+                    xcb::Point::new(self.end.x() + dx, self.end.y() + dy)
+                } else {
+                    clamp_to_bounds(
+                        self.bounds,
+                        xcb::Point::new(self.end.x() + dx, self.end.y() + dy),
+                    )
+                };
+                self.end = moved;
+                self.cursor = moved;
+                self.update_selection();
+
+                // Keep the pointer on the active corner so that mouse motion
+                // carries on from where the keyboard left off
+                Outcome::Continue(Update {
+                    shape: Some(self.current_shape()),
+                    warp: Some(moved),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn test_config() -> SelectionConfig {
+    SelectionConfig {
+        screen: xcb::Rectangle::new(0, 0, 1920, 1080),
+        aspect: None,
+        monitors: vec![],
+        select_thickness: 1,
+        guide_thickness: 1,
+        guides: true,
+        initial: None,
+        edit: false,
+        snap: 0,
+        windows: vec![],
+        round_to: 0,
+        min_size: None,
+        max_size: None,
+        multi: false,
+    }
+}
+
+#[cfg(test)]
+fn test_state_with(config: SelectionConfig) -> SelectionState {
+    SelectionState::new(config, xcb::Point::new(100, 100))
+}
+
+#[cfg(test)]
+fn test_state() -> SelectionState {
+    test_state_with(test_config())
+}
+
+#[cfg(test)]
+fn pt(x: i16, y: i16) -> xcb::Point {
+    xcb::Point::new(x, y)
+}
+
+#[cfg(test)]
+fn motion(x: i16, y: i16) -> Input {
+    Input::Motion(pt(x, y), Modifiers::default())
+}
+
+#[cfg(test)]
+fn done_region(outcome: Outcome) -> (i16, i16, u16, u16) {
+    match outcome {
+        Outcome::Done(Selection::Region(r)) => (r.x(), r.y(), r.width(), r.height()),
+        _ => panic!("expected a finished region"),
+    }
+}
+
+#[cfg(test)]
+fn done_point(outcome: Outcome) -> (i16, i16) {
+    match outcome {
+        Outcome::Done(Selection::Point(p)) => (p.x(), p.y()),
+        _ => panic!("expected a finished click"),
+    }
+}
+
+#[test]
+fn test_drag_selection() {
+    let mut state = test_state();
+    state.handle(Input::ButtonPress(1, pt(50, 60)));
+    state.handle(motion(20, 100));
+    assert_eq!(
+        done_region(state.handle(Input::ButtonRelease(1))),
+        (20, 60, 30, 40)
+    );
+}
+
+#[test]
+fn test_click_picks_window() {
+    let mut state = test_state();
+    state.handle(Input::ButtonPress(1, pt(50, 60)));
+    assert_eq!(done_point(state.handle(Input::ButtonRelease(1))), (50, 60));
+
+    let mut state = test_state();
+    state.handle(Input::ButtonPress(1, pt(50, 60)));
+    state.handle(motion(50, 60));
+    assert_eq!(done_point(state.handle(Input::ButtonRelease(1))), (50, 60));
+}
+
+#[test]
+fn test_scroll_restarts_selection() {
+    let mut state = test_state();
+    state.handle(Input::ButtonPress(1, pt(50, 60)));
+    state.handle(motion(200, 200));
+    assert!(state.in_selection());
+
+    state.handle(Input::ButtonPress(SCROLL_UP, pt(200, 200)));
+    assert!(!state.in_selection());
+    state.handle(Input::ButtonRelease(SCROLL_UP));
+    state.handle(motion(300, 300));
+
+    // The release of the button held before scrolling is ignored
+    assert!(matches!(
+        state.handle(Input::ButtonRelease(1)),
+        Outcome::Continue(_)
+    ));
+
+    state.handle(Input::ButtonPress(1, pt(10, 10)));
+    state.handle(motion(20, 30));
+    assert_eq!(
+        done_region(state.handle(Input::ButtonRelease(1))),
+        (10, 10, 10, 20)
+    );
+}
+
+#[test]
+fn test_hover_outline() {
+    let mut state = test_state();
+    state.set_hover(Some(xcb::Rectangle::new(10, 10, 100, 100)));
+    match state.handle(motion(50, 50)) {
+        Outcome::Continue(update) => assert_eq!(update.shape.unwrap().len(), 6),
+        _ => panic!("expected to continue"),
+    }
+
+    // No hover outline while dragging
+    state.handle(Input::ButtonPress(1, pt(50, 50)));
+    match state.handle(motion(60, 60)) {
+        Outcome::Continue(update) => assert_eq!(update.shape.unwrap().len(), 4),
+        _ => panic!("expected to continue"),
+    }
+}
+
+#[test]
+fn test_monitors() {
+    let mut state = test_state_with(SelectionConfig {
+        screen: xcb::Rectangle::new(0, 0, 3840, 1080),
+        monitors: vec![
+            xcb::Rectangle::new(0, 0, 1920, 1080),
+            xcb::Rectangle::new(1920, 0, 1920, 1080),
+        ],
+        ..test_config()
+    });
+
+    // Guides only span the monitor under the pointer
+    match state.handle(motion(2000, 500)) {
+        Outcome::Continue(update) => {
+            let shape = update.shape.unwrap();
+            assert_eq!((shape[1].x(), shape[1].width()), (1920, 1920));
+        }
+        _ => panic!("expected to continue"),
+    }
+
+    // Selections are clamped to the monitor they started on
+    state.handle(Input::ButtonPress(1, pt(1800, 100)));
+    state.handle(motion(2500, 200));
+    assert_eq!(
+        done_region(state.handle(Input::ButtonRelease(1))),
+        (1800, 100, 120, 100)
+    );
+}
+
+#[test]
+fn test_aspect_ratio() {
+    let mut state = test_state_with(SelectionConfig {
+        aspect: Some((16, 9)),
+        ..test_config()
+    });
+    state.handle(Input::ButtonPress(1, pt(100, 100)));
+    state.handle(motion(260, 120));
+    assert_eq!(
+        done_region(state.handle(Input::ButtonRelease(1))),
+        (100, 100, 160, 90)
+    );
+
+    // Growing up and to the left, limited by the edge of the screen
+    let mut state = test_state_with(SelectionConfig {
+        aspect: Some((16, 9)),
+        ..test_config()
+    });
+    state.handle(Input::ButtonPress(1, pt(100, 100)));
+    state.handle(motion(90, 0));
+    assert_eq!(
+        done_region(state.handle(Input::ButtonRelease(1))),
+        (0, 44, 100, 56)
+    );
+
+    // Holding Shift keeps a square
+    let mut state = test_state();
+    state.handle(Input::ButtonPress(1, pt(100, 100)));
+    state.handle(Input::Motion(
+        pt(150, 120),
+        Modifiers {
+            shift: true,
+            control: false,
+        },
+    ));
+    assert_eq!(
+        done_region(state.handle(Input::ButtonRelease(1))),
+        (100, 100, 50, 50)
+    );
+}
+
+#[test]
+fn test_aspect_ratio_flat_drag() {
+    let mut state = test_state_with(SelectionConfig {
+        aspect: Some((16, 9)),
+        ..test_config()
+    });
+    state.handle(Input::ButtonPress(1, pt(100, 100)));
+    state.handle(motion(260, 100));
+    assert_eq!(
+        done_region(state.handle(Input::ButtonRelease(1))),
+        (100, 100, 160, 90)
+    );
+
+    let mut state = test_state_with(SelectionConfig {
+        aspect: Some((16, 9)),
+        ..test_config()
+    });
+    state.handle(Input::ButtonPress(1, pt(300, 300)));
+    state.handle(motion(300, 210));
+    assert_eq!(
+        done_region(state.handle(Input::ButtonRelease(1))),
+        (300, 210, 160, 90)
+    );
+}
+
+#[test]
+fn test_surround() {
+    let screen = xcb::Rectangle::new(0, 0, 100, 100);
+    let tuples = |rects: Vec<xcb::Rectangle>| {
+        rects
+            .iter()
+            .map(|r| (r.x(), r.y(), r.width(), r.height()))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        tuples(surround(screen, xcb::Rectangle::new(10, 20, 30, 40))),
+        vec![
+            (0, 0, 100, 20),
+            (0, 20, 10, 40),
+            (40, 20, 60, 40),
+            (0, 60, 100, 40)
+        ]
+    );
+    // A hole touching the edges leaves out empty rectangles
+    assert_eq!(
+        tuples(surround(screen, xcb::Rectangle::new(0, 0, 50, 100))),
+        vec![(50, 0, 50, 100)]
+    );
+    // An empty hole covers everything
+    assert_eq!(
+        tuples(surround(screen, xcb::Rectangle::new(10, 10, 0, 0))),
+        vec![(0, 0, 100, 100)]
+    );
+}
+
+#[test]
+fn test_cancel() {
+    let mut state = test_state();
+    state.handle(Input::ButtonPress(1, pt(50, 60)));
+    assert!(matches!(
+        state.handle(Input::ButtonPress(RIGHT_BUTTON, pt(50, 60))),
+        Outcome::Cancelled(Cancel::RightClick)
+    ));
+
+    let mut state = test_state();
+    assert!(matches!(
+        state.handle(Input::Key(Key::Escape, Modifiers::default())),
+        Outcome::Cancelled(Cancel::Escape)
+    ));
+}
+
+#[test]
+fn test_keyboard_adjustment() {
+    let shift = Modifiers {
+        shift: true,
+        control: false,
+    };
+    let control = Modifiers {
+        shift: false,
+        control: true,
+    };
+
+    let mut state = test_state();
+    state.handle(Input::ButtonPress(1, pt(10, 10)));
+    state.handle(motion(20, 20));
+    state.handle(Input::Key(Key::Right, Modifiers::default()));
+    state.handle(Input::Key(Key::Down, shift));
+    state.handle(Input::Key(Key::Left, control));
+    assert_eq!(
+        done_region(state.handle(Input::Key(Key::Return, Modifiers::default()))),
+        (9, 10, 11, 20)
+    );
+
+    // Moving with Ctrl stops at the edge of the screen
+    let mut state = test_state();
+    state.handle(Input::ButtonPress(1, pt(0, 10)));
+    state.handle(motion(20, 20));
+    state.handle(Input::Key(Key::Left, control));
+    match state.handle(Input::Key(Key::Left, control)) {
+        Outcome::Continue(update) => {
+            let warp = update.warp.unwrap();
+            assert_eq!((warp.x(), warp.y()), (20, 20));
+        }
+        _ => panic!("expected to continue"),
+    }
+    assert_eq!(
+        done_region(state.handle(Input::Key(Key::Return, Modifiers::default()))),
+        (0, 10, 20, 10)
+    );
+
+    // And at the edge of the monitor the selection started on
+    let mut state = test_state_with(SelectionConfig {
+        monitors: vec![
+            xcb::Rectangle::new(0, 0, 960, 1080),
+            xcb::Rectangle::new(960, 0, 960, 1080),
+        ],
+        ..test_config()
+    });
+    state.handle(Input::ButtonPress(1, pt(900, 10)));
+    state.handle(motion(950, 20));
+    state.handle(Input::Key(Key::Right, shift));
+    match state.handle(Input::Key(Key::Right, shift)) {
+        Outcome::Continue(update) => assert_eq!(update.warp.unwrap().x(), 960),
+        _ => panic!("expected to continue"),
+    }
+    state.handle(Input::Key(Key::Right, control));
+    assert_eq!(
+        done_region(state.handle(Input::Key(Key::Return, Modifiers::default()))),
+        (900, 10, 60, 10)
+    );
+
+    // Enter before pressing a button picks the window under the pointer
+    let mut state = test_state();
+    state.handle(Input::Key(Key::Up, Modifiers::default()));
+    assert_eq!(
+        done_point(state.handle(Input::Key(Key::Return, Modifiers::default()))),
+        (100, 99)
+    );
+}
+
+#[test]
+fn test_initial_selection() {
+    let initial = || {
+        let config = SelectionConfig {
+            initial: Some(xcb::Rectangle::new(10, 20, 300, 200)),
+            ..test_config()
+        };
+        SelectionState::new(config, pt(500, 500))
+    };
+
+    // Shown right away with its handles, and not disturbed by the pointer
+    let mut state = initial();
+    assert!(state.in_selection());
+    assert_eq!(state.initial_shape().len(), 12);
+    state.handle(motion(600, 600));
+    assert_eq!(
+        done_region(state.handle(Input::Key(Key::Return, Modifiers::default()))),
+        (10, 20, 300, 200)
+    );
+
+    // Dragging from inside moves it, but keeps it on screen
+    let mut state = initial();
+    state.handle(motion(100, 100));
+    state.handle(Input::ButtonPress(1, pt(100, 100)));
+    state.handle(motion(50, 150));
+    state.handle(motion(0, 150));
+    assert!(matches!(
+        state.handle(Input::ButtonRelease(1)),
+        Outcome::Continue(_)
+    ));
+    // Arrow keys resize it from the bottom right corner
+    state.handle(Input::Key(Key::Right, Modifiers::default()));
+    assert_eq!(
+        done_region(state.handle(Input::Key(Key::Return, Modifiers::default()))),
+        (0, 70, 301, 200)
+    );
+
+    // Pressing outside of it starts over
+    let mut state = initial();
+    state.handle(Input::ButtonPress(1, pt(400, 400)));
+    state.handle(motion(420, 430));
+    assert_eq!(
+        done_region(state.handle(Input::ButtonRelease(1))),
+        (400, 400, 20, 30)
+    );
+}
+
+#[test]
+fn test_edit_mode() {
+    let mut state = test_state_with(SelectionConfig {
+        edit: true,
+        ..test_config()
+    });
+    state.handle(Input::ButtonPress(1, pt(100, 100)));
+    state.handle(motion(300, 200));
+    match state.handle(Input::ButtonRelease(1)) {
+        // The outline plus 8 handles
+        Outcome::Continue(update) => assert_eq!(update.shape.unwrap().len(), 12),
+        _ => panic!("expected to continue"),
+    }
+
+    // Dragging the right edge only changes the width
+    state.handle(Input::ButtonPress(1, pt(301, 150)));
+    state.handle(motion(350, 10));
+    state.handle(Input::ButtonRelease(1));
+    // Dragging the top left corner
+    state.handle(Input::ButtonPress(1, pt(99, 101)));
+    state.handle(motion(50, 60));
+    state.handle(Input::ButtonRelease(1));
+    // Dragging from inside moves it
+    state.handle(Input::ButtonPress(1, pt(200, 150)));
+    state.handle(motion(210, 170));
+    state.handle(Input::ButtonRelease(1));
+
+    state.handle(Input::ButtonPress(1, pt(200, 150)));
+    state.handle(Input::ButtonRelease(1));
+    assert_eq!(
+        done_region(state.handle(Input::DoubleClick(1, pt(200, 150)))),
+        (60, 80, 300, 140)
+    );
+
+    // Clicks still pick windows right away
+    let mut state = test_state_with(SelectionConfig {
+        edit: true,
+        ..test_config()
+    });
+    state.handle(Input::ButtonPress(1, pt(50, 60)));
+    assert_eq!(done_point(state.handle(Input::ButtonRelease(1))), (50, 60));
+}
+
+#[test]
+fn test_snap() {
+    let mut state = test_state_with(SelectionConfig {
+        snap: 8,
+        windows: vec![
+            xcb::Rectangle::new(100, 100, 200, 100),
+            xcb::Rectangle::new(300, 100, 200, 100),
+        ],
+        ..test_config()
+    });
+
+    // Corners land on the nearest edges within reach
+    state.handle(Input::ButtonPress(1, pt(95, 104)));
+    state.handle(motion(494, 190));
+    assert_eq!(
+        done_region(state.handle(Input::ButtonRelease(1))),
+        (100, 100, 400, 90)
+    );
+
+    // Edges far away from the selection don't count
+    let mut state = test_state_with(SelectionConfig {
+        snap: 8,
+        windows: vec![xcb::Rectangle::new(100, 500, 200, 100)],
+        ..test_config()
+    });
+    state.handle(Input::ButtonPress(1, pt(95, 10)));
+    state.handle(motion(120, 20));
+    assert_eq!(
+        done_region(state.handle(Input::ButtonRelease(1))),
+        (95, 10, 25, 10)
+    );
+
+    // Holding Ctrl turns it off
+    let mut state = test_state_with(SelectionConfig {
+        snap: 8,
+        windows: vec![xcb::Rectangle::new(100, 100, 200, 100)],
+        ..test_config()
+    });
+    state.handle(Input::ButtonPress(1, pt(10, 10)));
+    state.handle(Input::Motion(
+        pt(295, 195),
+        Modifiers {
+            shift: false,
+            control: true,
+        },
+    ));
+    assert_eq!(
+        done_region(state.handle(Input::ButtonRelease(1))),
+        (10, 10, 285, 185)
+    );
+
+    // A click still picks the window it was on
+    let mut state = test_state_with(SelectionConfig {
+        snap: 8,
+        windows: vec![xcb::Rectangle::new(100, 100, 200, 100)],
+        ..test_config()
+    });
+    state.handle(Input::ButtonPress(1, pt(96, 150)));
+    assert_eq!(done_point(state.handle(Input::ButtonRelease(1))), (96, 150));
+}
+
+#[test]
+fn test_multi() {
+    let mut state = test_state_with(SelectionConfig {
+        multi: true,
+        ..test_config()
+    });
+
+    state.handle(Input::ButtonPress(1, pt(10, 10)));
+    state.handle(motion(20, 20));
+    let first = done_region(state.handle(Input::ButtonRelease(1)));
+    assert_eq!(first, (10, 10, 10, 10));
+    state.keep(xcb::Rectangle::new(10, 10, 10, 10));
+    assert!(!state.in_selection());
+
+    // The first one stays on screen while making the next
+    state.handle(Input::ButtonPress(1, pt(100, 100)));
+    match state.handle(motion(200, 200)) {
+        Outcome::Continue(update) => assert_eq!(update.shape.unwrap().len(), 8),
+        _ => panic!("expected to continue"),
+    }
+    state.handle(Input::ButtonRelease(1));
+    state.keep(xcb::Rectangle::new(100, 100, 100, 100));
+
+    assert!(matches!(
+        state.handle(Input::Key(Key::Return, Modifiers::default())),
+        Outcome::Finished
+    ));
+
+    // Enter while still dragging keeps the region, and letting go of the
+    // button afterwards doesn't pick the window under the pointer as well
+    let mut state = test_state_with(SelectionConfig {
+        multi: true,
+        ..test_config()
+    });
+    state.handle(Input::ButtonPress(1, pt(10, 10)));
+    state.handle(motion(50, 50));
+    let region = done_region(state.handle(Input::Key(Key::Return, Modifiers::default())));
+    assert_eq!(region, (10, 10, 40, 40));
+    state.keep(xcb::Rectangle::new(10, 10, 40, 40));
+    assert!(matches!(
+        state.handle(Input::ButtonRelease(1)),
+        Outcome::Continue(_)
+    ));
+    assert!(!state.in_selection());
+    // The next click works as usual
+    state.handle(Input::ButtonPress(1, pt(300, 300)));
+    assert_eq!(
+        done_point(state.handle(Input::ButtonRelease(1))),
+        (300, 300)
+    );
+}
+
+#[test]
+fn test_round_to() {
+    assert_eq!(round_length(807, 2, 1920), 808);
+    assert_eq!(round_length(806, 2, 1920), 806);
+    assert_eq!(round_length(5, 4, 1920), 4);
+    assert_eq!(round_length(6, 4, 1920), 8);
+    assert_eq!(round_length(1, 16, 1920), 16);
+    assert_eq!(round_length(0, 16, 1920), 0);
+    assert_eq!(round_length(1919, 2, 1919), 1918);
+    assert_eq!(round_length(7, 16, 10), 7);
+    assert_eq!(round_length(807, 1, 1920), 807);
+
+    let mut state = test_state_with(SelectionConfig {
+        round_to: 16,
+        ..test_config()
+    });
+    state.handle(Input::ButtonPress(1, pt(100, 100)));
+    state.handle(motion(131, 107));
+    assert_eq!(state.selection.width(), 32);
+    assert_eq!(state.selection.height(), 16);
+    // Dragging up and left rounds towards the pointer too
+    state.handle(motion(61, 93));
+    assert_eq!(
+        done_region(state.handle(Input::ButtonRelease(1))),
+        (68, 84, 32, 16)
+    );
+
+    // Stays on the screen
+    let mut state = test_state_with(SelectionConfig {
+        round_to: 16,
+        ..test_config()
+    });
+    state.handle(Input::ButtonPress(1, pt(1900, 1070)));
+    state.handle(motion(1919, 1079));
+    assert_eq!(
+        done_region(state.handle(Input::ButtonRelease(1))),
+        (1900, 1070, 16, 9)
+    );
+}
+
+#[test]
+fn test_round_to_aspect() {
+    // 16:9 with even sides comes in steps of 32x18, rather than rounding
+    // 162x91 to 162x92
+    let mut state = test_state_with(SelectionConfig {
+        aspect: Some((16, 9)),
+        round_to: 2,
+        ..test_config()
+    });
+    state.handle(Input::ButtonPress(1, pt(100, 100)));
+    state.handle(motion(262, 120));
+    assert_eq!(
+        done_region(state.handle(Input::ButtonRelease(1))),
+        (100, 100, 160, 90)
+    );
+
+    // Up and to the left, as far as the screen allows
+    let mut state = test_state_with(SelectionConfig {
+        aspect: Some((32, 18)),
+        round_to: 2,
+        ..test_config()
+    });
+    state.handle(Input::ButtonPress(1, pt(100, 100)));
+    state.handle(motion(0, 0));
+    assert_eq!(
+        done_region(state.handle(Input::ButtonRelease(1))),
+        (4, 46, 96, 54)
+    );
+
+    // A square made with Shift stays square
+    let mut state = test_state_with(SelectionConfig {
+        round_to: 16,
+        ..test_config()
+    });
+    state.handle(Input::ButtonPress(1, pt(100, 100)));
+    state.handle(Input::Motion(
+        pt(150, 120),
+        Modifiers {
+            shift: true,
+            control: false,
+        },
+    ));
+    assert_eq!(
+        done_region(state.handle(Input::ButtonRelease(1))),
+        (100, 100, 48, 48)
+    );
+
+    // When no multiple fits the ratio, rounding wins
+    let mut state = test_state_with(SelectionConfig {
+        aspect: Some((16, 9)),
+        round_to: 2,
+        ..test_config()
+    });
+    state.handle(Input::ButtonPress(1, pt(1900, 1070)));
+    state.handle(motion(1919, 1079));
+    assert_eq!(
+        done_region(state.handle(Input::ButtonRelease(1))),
+        (1900, 1070, 18, 10)
+    );
+}
+
+#[test]
+fn test_size_limits() {
+    let mut state = test_state_with(SelectionConfig {
+        min_size: Some((20, 10)),
+        max_size: Some((100, 50)),
+        ..test_config()
+    });
+
+    // An accidental drag is thrown away
+    state.handle(Input::ButtonPress(1, pt(100, 100)));
+    state.handle(motion(103, 102));
+    assert!(state.at_limit());
+    match state.handle(Input::ButtonRelease(1)) {
+        Outcome::Continue(update) => assert!(update.shape.is_some()),
+        _ => panic!("expected the selection to be rejected"),
+    }
+    assert!(!state.in_selection());
+    assert!(!state.at_limit());
+
+    // Clicks still pick windows
+    state.handle(Input::ButtonPress(1, pt(100, 100)));
+    assert_eq!(
+        done_point(state.handle(Input::ButtonRelease(1))),
+        (100, 100)
+    );
+
+    let mut state = test_state_with(SelectionConfig {
+        min_size: Some((20, 10)),
+        max_size: Some((100, 50)),
+        ..test_config()
+    });
+    state.handle(Input::ButtonPress(1, pt(100, 100)));
+    state.handle(motion(150, 130));
+    assert!(!state.at_limit());
+    state.handle(motion(300, 20));
+    assert!(state.at_limit());
+    assert_eq!(
+        done_region(state.handle(Input::ButtonRelease(1))),
+        (100, 50, 100, 50)
+    );
+
+    // Both sides shrink to keep the aspect ratio
+    let mut state = test_state_with(SelectionConfig {
+        aspect: Some((16, 9)),
+        max_size: Some((100, 100)),
+        ..test_config()
+    });
+    state.handle(Input::ButtonPress(1, pt(100, 100)));
+    state.handle(motion(420, 280));
+    assert!(state.at_limit());
+    assert_eq!(
+        done_region(state.handle(Input::ButtonRelease(1))),
+        (100, 100, 100, 56)
+    );
+
+    // Shrinking a selection in edit mode doesn't throw it away
+    let mut state = test_state_with(SelectionConfig {
+        edit: true,
+        min_size: Some((20, 10)),
+        ..test_config()
+    });
+    state.handle(Input::ButtonPress(1, pt(100, 100)));
+    state.handle(motion(200, 150));
+    state.handle(Input::ButtonRelease(1));
+    state.handle(Input::ButtonPress(1, pt(200, 150)));
+    state.handle(motion(105, 105));
+    state.handle(Input::ButtonRelease(1));
+    assert!(matches!(
+        state.handle(Input::Key(Key::Return, Modifiers::default())),
+        Outcome::Continue(_)
+    ));
+    assert!(state.in_selection());
+    assert!(state.at_limit());
+    // Until it is big enough again
+    state.handle(Input::ButtonPress(1, pt(105, 105)));
+    state.handle(motion(150, 130));
+    state.handle(Input::ButtonRelease(1));
+    assert!(!state.at_limit());
+    assert_eq!(
+        done_region(state.handle(Input::Key(Key::Return, Modifiers::default()))),
+        (100, 100, 50, 30)
+    );
+}
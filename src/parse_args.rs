@@ -0,0 +1,583 @@
+use crate::config::{self, Config};
+use hacksaw::parse_format::{parse_format_string, Format};
+use hacksaw::{parse_geometry, Extents, Lasso, Options};
+
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use structopt::clap::{self, AppSettings};
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+#[structopt(
+    name = "hacksaw",
+    max_term_width = 80,
+    // Options from the configuration file come first, so let the command
+    // line replace them
+    setting = AppSettings::AllArgsOverrideSelf
+)]
+pub(crate) struct Opt {
+    #[structopt(
+        short = "n",
+        long = "no-guides",
+        help = "Disable fighter pilot guide lines"
+    )]
+    pub(crate) no_guides: bool,
+
+    #[structopt(
+        short = "g",
+        long = "guide-thickness",
+        default_value = "1",
+        help = "Thickness of fighter pilot guide lines"
+    )]
+    pub(crate) guide_thickness: u16,
+
+    #[structopt(
+        short = "s",
+        long = "select-thickness",
+        default_value = "1",
+        help = "Thickness of selection box lines"
+    )]
+    pub(crate) select_thickness: u16,
+
+    #[structopt(
+        short = "c",
+        long = "colour",
+        default_value = "#7f7f7f",
+        parse(try_from_str = parse_hex),
+        help = "Hex colour of the lines (RGB or RGBA), '#' optional"
+    )]
+    pub(crate) line_colour: u32,
+
+    #[structopt(
+        short = "d",
+        long = "dim",
+        help = "Shade everything outside the selection (needs a compositor)"
+    )]
+    pub(crate) dim: bool,
+
+    #[structopt(
+        short = "l",
+        long = "label",
+        help = "Show the size and position of the selection next to the pointer"
+    )]
+    pub(crate) label: bool,
+
+    #[structopt(
+        long = "dim-colour",
+        default_value = "#00000080",
+        parse(try_from_str = parse_hex),
+        help = "Hex colour of the shade (RGB or RGBA), '#' optional"
+    )]
+    pub(crate) dim_colour: u32,
+
+    #[structopt(
+        long = "limit-colour",
+        default_value = "#ff0000",
+        parse(try_from_str = parse_hex),
+        help = "Hex colour of the lines while the selection is under \
+                --min-size or held back by --max-size (RGB or RGBA), '#' optional"
+    )]
+    pub(crate) limit_colour: u32,
+
+    #[structopt(
+        short = "f",
+        long = "format",
+        default_value = "%g",
+        parse(try_from_str = parse_format_string),
+        allow_hyphen_values = true,
+        help = "\
+Output format. You can use:
+      %x for x-coordinate,
+      %y for y-coordinate,
+      %w for width,
+      %h for height,
+      %i for selected window id,
+      %X for the window id in hex, like 0x01e00003,
+      %g as a shorthand for %wx%h+%x+%y (X geometry),
+      %G for the geometry as WxH-X-Y, with X and Y counted from
+         the right and bottom of the screen and 0 past them,
+      %r for the right edge (%x + %w),
+      %b for the bottom edge (%y + %h),
+      %C for the centre as X,Y,
+      %a for the area (%w * %h),
+      %m for the name of the monitor the selection is on,
+      %M for the geometry relative to that monitor,
+      %c for the class of the selected window,
+      %n for its instance name,
+      %t for its title,
+      %p for the ID of the process it belongs to,
+      %d for the desktop it is on,
+      %W for the windows in a selection made with --lasso,
+         one \"id WxH+X+Y\" line each,
+      %j for all of the above as a JSON object,
+      %% for a literal '%'.
+Other %-codes will cause an error.
+A width between the '%' and the letter pads the value, '-' aligns
+it left and '0' fills numbers with zeros, e.g. %05x or %-6w.
+Missing values, like %p without a pid, only get spaces."
+    )]
+    pub(crate) format: Format,
+
+    #[structopt(
+        short = "j",
+        long = "json",
+        help = "Output a JSON object, same as --format %j"
+    )]
+    pub(crate) json: bool,
+
+    #[structopt(
+        short = "r",
+        long = "remove-decorations",
+        default_value = "0",
+        help = "Number of (nested) window manager frames to try and remove"
+    )]
+    pub(crate) remove_decorations: u32,
+
+    #[structopt(
+        short = "m",
+        long = "monitors",
+        help = "Keep guides and selections on the monitor under the pointer, \
+                and select the whole monitor when clicking on the desktop"
+    )]
+    pub(crate) monitors: bool,
+
+    #[structopt(
+        short = "a",
+        long = "aspect",
+        parse(try_from_str = parse_aspect),
+        help = "Keep selections at this aspect ratio, e.g. 16:9. \
+                Without it, hold Shift while dragging to keep a square"
+    )]
+    pub(crate) aspect: Option<(u16, u16)>,
+
+    #[structopt(
+        short = "z",
+        long = "magnify",
+        help = "Show a zoomed in view of the pixels around the pointer"
+    )]
+    pub(crate) magnify: bool,
+
+    #[structopt(
+        long = "zoom",
+        default_value = "8",
+        parse(try_from_str = parse_zoom),
+        help = "Zoom factor of the magnifier, from 2 to 16"
+    )]
+    pub(crate) zoom: u16,
+
+    #[structopt(
+        long = "capture",
+        help = "Save a screenshot of the selection to this file, as PPM if it \
+                ends in .ppm and PNG otherwise. Use - for PNG on stdout, \
+                which replaces the usual output"
+    )]
+    pub(crate) capture: Option<String>,
+
+    #[structopt(
+        long = "initial-geometry",
+        parse(try_from_str = parse_geometry),
+        help = "Start with this region (WxH+X+Y, as printed by %g) selected. \
+                Drag it to move it, use the arrow keys to adjust it and \
+                Enter to confirm, or draw a new one"
+    )]
+    pub(crate) initial_geometry: Option<xcb::Rectangle>,
+
+    #[structopt(
+        short = "e",
+        long = "edit",
+        help = "Keep the selection on screen after dragging, to move it or \
+                resize it with its handles. Confirm with Enter or a double click"
+    )]
+    pub(crate) edit: bool,
+
+    #[structopt(
+        long = "snap",
+        default_value = "0",
+        help = "Snap the selection to window edges within this many pixels. \
+                Hold Ctrl while dragging to not snap"
+    )]
+    pub(crate) snap: u16,
+
+    #[structopt(
+        long = "round-to",
+        default_value = "1",
+        help = "Keep the width and height multiples of this while \
+                dragging, keeping any --aspect too"
+    )]
+    pub(crate) round_to: u16,
+
+    #[structopt(
+        long = "round-result",
+        default_value = "1",
+        help = "Round the width and height of the final selection to \
+                multiples of this, e.g. 2 for video encoders that need even \
+                sizes. Applies to picked windows and monitors too, but not \
+                to --aspect"
+    )]
+    pub(crate) round_result: u16,
+
+    #[structopt(
+        long = "min-size",
+        parse(try_from_str = parse_size),
+        help = "Throw away dragged selections smaller than WxH, to be \
+                dragged again. Clicks still pick windows"
+    )]
+    pub(crate) min_size: Option<(u16, u16)>,
+
+    #[structopt(
+        long = "max-size",
+        parse(try_from_str = parse_size),
+        help = "Keep the selection from growing beyond WxH"
+    )]
+    pub(crate) max_size: Option<(u16, u16)>,
+
+    #[structopt(
+        long = "lasso",
+        parse(try_from_str = parse_lasso),
+        help = "List the windows a dragged selection intersects, or with \
+                'contained' only those fully inside of it, for %W and %j"
+    )]
+    pub(crate) lasso: Option<Lasso>,
+
+    #[structopt(
+        long = "multi",
+        conflicts_with = "capture",
+        help = "Make any number of selections, keeping them on screen, and \
+                press Enter when done. Prints one line per selection, or a \
+                JSON array with --json"
+    )]
+    pub(crate) multi: bool,
+
+    #[structopt(
+        long = "ewmh",
+        parse(try_from_str = parse_extents),
+        help = "Pick windows the way the window manager stacks them, selecting \
+                their 'content' (without client-side shadows), the 'client' \
+                window, or the 'frame' including decorations. Overrides -r"
+    )]
+    pub(crate) ewmh: Option<Extents>,
+
+    #[structopt(
+        short = "D",
+        long = "descend",
+        help = "Pick the deepest window under the pointer instead of a \
+                top-level one. Hold Shift and scroll to walk up and down"
+    )]
+    pub(crate) descend: bool,
+
+    #[structopt(
+        long = "profile",
+        help = "Use the settings of this [profile.<name>] from \
+                $XDG_CONFIG_HOME/hacksaw/config.toml. Flags turned on \
+                there are turned off with --no-<flag>, or --guides"
+    )]
+    pub(crate) profile: Option<String>,
+
+    // Turn off flags set in the configuration file
+    #[structopt(long = "guides", hidden = true)]
+    guides: bool,
+    #[structopt(long = "no-dim", hidden = true)]
+    no_dim: bool,
+    #[structopt(long = "no-label", hidden = true)]
+    no_label: bool,
+    #[structopt(long = "no-monitors", hidden = true)]
+    no_monitors: bool,
+    #[structopt(long = "no-magnify", hidden = true)]
+    no_magnify: bool,
+    #[structopt(long = "no-edit", hidden = true)]
+    no_edit: bool,
+    #[structopt(long = "no-multi", hidden = true)]
+    no_multi: bool,
+    #[structopt(long = "no-descend", hidden = true)]
+    no_descend: bool,
+}
+
+/// Options that, when given on the command line, replace another one in the
+/// configuration file besides themselves.
+const REPLACES: &[(&str, &str)] = &[
+    ("format", "json"),
+    ("json", "format"),
+    ("capture", "multi"),
+    ("multi", "capture"),
+];
+
+impl Opt {
+    /// Parse the command line, on top of the options set in the
+    /// configuration file.
+    pub(crate) fn load() -> Opt {
+        Opt::with_config(env::args_os().collect(), config::load).unwrap_or_else(|err| err.exit())
+    }
+
+    /// Parse `args` on top of the configuration `load`s.
+    fn with_config(
+        args: Vec<OsString>,
+        load: impl FnOnce() -> Result<Config, String>,
+    ) -> clap::Result<Opt> {
+        let matches = Opt::clap().get_matches_from_safe(&args)?;
+        let opt = Opt::from_clap(&matches);
+
+        let config_error =
+            |err: String| clap::Error::with_description(&err, clap::ErrorKind::InvalidValue);
+        let replaced = |key: &str| {
+            REPLACES
+                .iter()
+                .any(|&(option, other)| other == key && matches.occurrences_of(option) > 0)
+        };
+        let config_args = load()
+            .and_then(|config| config.args(opt.profile.as_deref(), replaced))
+            .map_err(config_error)?;
+        if config_args.is_empty() {
+            return opt.negated().validated();
+        }
+
+        // The command line comes last, so that it wins
+        let (name, args) = args.split_at(args.len().min(1));
+        let args = name
+            .iter()
+            .cloned()
+            .chain(config_args)
+            .chain(args.iter().cloned());
+        let matches = Opt::clap().get_matches_from_safe(args)?;
+        Opt::from_clap(&matches).negated().validated()
+    }
+
+    /// Check the options that only make sense together.
+    fn validated(self) -> clap::Result<Opt> {
+        if let (Some(min), Some(max)) = (self.min_size, self.max_size) {
+            if min.0 > max.0 || min.1 > max.1 {
+                return Err(clap::Error::with_description(
+                    &format!(
+                        "--min-size {}x{} is larger than --max-size {}x{}, \
+                         so no selection could ever be made",
+                        min.0, min.1, max.0, max.1
+                    ),
+                    clap::ErrorKind::ValueValidation,
+                ));
+            }
+        }
+        Ok(self)
+    }
+
+    /// Apply the flags that turn others off.
+    fn negated(mut self) -> Opt {
+        self.no_guides &= !self.guides;
+        self.dim &= !self.no_dim;
+        self.label &= !self.no_label;
+        self.monitors &= !self.no_monitors;
+        self.magnify &= !self.no_magnify;
+        self.edit &= !self.no_edit;
+        self.multi &= !self.no_multi;
+        self.descend &= !self.no_descend;
+        self
+    }
+
+    pub(crate) fn options(&self) -> Options {
+        let mut options = Options::default();
+        options.guides = !self.no_guides;
+        options.guide_thickness = self.guide_thickness;
+        options.select_thickness = self.select_thickness;
+        options.line_colour = self.line_colour;
+        options.limit_colour = self.limit_colour;
+        options.remove_decorations = self.remove_decorations;
+        options.monitors = self.monitors;
+        options.aspect = self.aspect;
+        options.label = self.label;
+        options.dim = if self.dim {
+            Some(self.dim_colour)
+        } else {
+            None
+        };
+        options.magnify = if self.magnify { Some(self.zoom) } else { None };
+        options.initial_geometry = self.initial_geometry;
+        options.edit = self.edit;
+        options.snap = self.snap;
+        options.round_to = self.round_to;
+        options.round_result = self.round_result;
+        options.min_size = self.min_size;
+        options.max_size = self.max_size;
+        options.lasso = self.lasso;
+        options.ewmh = self.ewmh;
+        options.descend = self.descend;
+        options.on_warning = |what, err| eprintln!("Not showing the {}: {}", what, err);
+        options
+    }
+}
+
+#[derive(Debug)]
+struct ParseHexError<'a> {
+    reason: String,
+    source: &'a str,
+}
+
+impl<'a> fmt::Display for ParseHexError<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Could not parse \"{}\": {}", self.source, self.reason)
+    }
+}
+
+fn parse_hex_slice(slice: &str) -> Result<u32, ParseHexError<'_>> {
+    u32::from_str_radix(slice, 16).map_err(|err| ParseHexError {
+        reason: err.to_string(),
+        source: slice,
+    })
+}
+
+/// Parse an HTML-color-like hex input
+fn parse_hex(hex: &str) -> Result<u32, ParseHexError<'_>> {
+    let hex = hex.trim_start_matches('#');
+    let mut color;
+
+    match hex.len() {
+        3 | 4 => {
+            color = 0x11 * parse_hex_slice(&hex[2..3])?
+                + 0x11_00 * parse_hex_slice(&hex[1..2])?
+                + 0x11_00_00 * parse_hex_slice(&hex[0..1])?;
+
+            if hex.len() == 4 {
+                color |= 0x11_00_00_00 * parse_hex_slice(&hex[3..4])?
+            } else {
+                color |= 0xFF_00_00_00;
+            }
+        }
+
+        6 => {
+            color = parse_hex_slice(hex)? | 0xFF_00_00_00;
+        }
+
+        8 => {
+            // RRGGBBAA, like the short form, to AARRGGBB
+            color = parse_hex_slice(hex)?.rotate_right(8);
+        }
+
+        _ => {
+            return Err(ParseHexError {
+                reason: "Hex colour should have length 3, 4, 6, or 8".to_owned(),
+                source: hex,
+            })
+        }
+    }
+
+    Ok(color)
+}
+
+/// Parse a `W:H` aspect ratio
+fn parse_aspect(input: &str) -> Result<(u16, u16), String> {
+    let mut parts = input.splitn(2, ':');
+    let mut next = || {
+        parts
+            .next()
+            .and_then(|part| part.trim().parse::<u16>().ok())
+            .filter(|&part| part > 0)
+            .ok_or_else(|| format!("Could not parse \"{}\": expected W:H, e.g. 16:9", input))
+    };
+    Ok((next()?, next()?))
+}
+
+/// Parse a `WxH` size
+fn parse_size(input: &str) -> Result<(u16, u16), String> {
+    let mut parts = input.splitn(2, 'x');
+    let mut next = || {
+        parts
+            .next()
+            .and_then(|part| part.trim().parse::<u16>().ok())
+            .ok_or_else(|| format!("Could not parse \"{}\": expected WxH, e.g. 20x20", input))
+    };
+    Ok((next()?, next()?))
+}
+
+/// Parse a zoom factor for the magnifier
+fn parse_zoom(input: &str) -> Result<u16, String> {
+    input
+        .parse::<u16>()
+        .ok()
+        .filter(|zoom| (2..=16).contains(zoom))
+        .ok_or_else(|| format!("Could not parse \"{}\": expected 2 to 16", input))
+}
+
+/// Parse the windows to list with --lasso
+fn parse_lasso(input: &str) -> Result<Lasso, String> {
+    match input {
+        "intersecting" => Ok(Lasso::Intersecting),
+        "contained" => Ok(Lasso::Contained),
+        _ => Err(format!(
+            "Could not parse \"{}\": expected intersecting or contained",
+            input
+        )),
+    }
+}
+
+/// Parse the part of windows to pick with --ewmh
+fn parse_extents(input: &str) -> Result<Extents, String> {
+    match input {
+        "content" => Ok(Extents::Content),
+        "client" => Ok(Extents::Client),
+        "frame" => Ok(Extents::Frame),
+        _ => Err(format!(
+            "Could not parse \"{}\": expected content, client or frame",
+            input
+        )),
+    }
+}
+
+#[cfg(test)]
+fn parse_with(config: &str, args: &[&str]) -> clap::Result<Opt> {
+    let args = std::iter::once("hacksaw")
+        .chain(args.iter().copied())
+        .map(OsString::from)
+        .collect();
+    Opt::with_config(args, || config::parse(config))
+}
+
+#[test]
+fn test_command_line_wins() {
+    use hacksaw::parse_format::FormatToken;
+
+    let flags = "dim = true\nno-guides = true\nedit = true\nmulti = true";
+    let opt = parse_with(flags, &[]).unwrap();
+    assert!(opt.dim && opt.no_guides && opt.edit && opt.multi);
+    let opt = parse_with(flags, &["--no-dim", "--guides", "--no-edit", "--no-multi"]).unwrap();
+    assert!(!opt.dim && !opt.no_guides && !opt.edit && !opt.multi);
+
+    let opt = parse_with("json = true", &["-f", "%x"]).unwrap();
+    assert!(!opt.json);
+    assert_eq!(opt.format, vec![FormatToken::X]);
+    let opt = parse_with("format = '%y'", &["--json"]).unwrap();
+    assert!(opt.json);
+
+    let opt = parse_with("multi = true", &["--capture", "shot.png"]).unwrap();
+    assert!(!opt.multi);
+    assert_eq!(opt.capture.as_deref(), Some("shot.png"));
+    let opt = parse_with("capture = 'shot.png'", &["--multi"]).unwrap();
+    assert!(opt.multi && opt.capture.is_none());
+    // Still a mistake on the command line alone
+    assert!(parse_with("", &["--multi", "--capture", "shot.png"]).is_err());
+
+    let opt = parse_with("colour = 'f00'", &["-c", "0f0"]).unwrap();
+    assert_eq!(opt.line_colour, 0xFF_00_FF_00);
+}
+
+#[test]
+fn test_parse_hex() {
+    assert_eq!(parse_hex("#f80").unwrap(), 0xFF_FF_88_00);
+    assert_eq!(parse_hex("f808").unwrap(), 0x88_FF_88_00);
+    assert_eq!(parse_hex("#ff8800").unwrap(), 0xFF_FF_88_00);
+    assert_eq!(parse_hex("ff880080").unwrap(), 0x80_FF_88_00);
+    assert!(parse_hex("#ff880").is_err());
+    assert!(parse_hex("#ggg").is_err());
+}
+
+#[test]
+fn test_size_limits() {
+    let opt = parse_with("", &["--min-size", "20x20", "--max-size", "100x20"]).unwrap();
+    assert_eq!(
+        (opt.min_size, opt.max_size),
+        (Some((20, 20)), Some((100, 20)))
+    );
+
+    match parse_with("", &["--min-size", "200x200", "--max-size", "100x100"]) {
+        Err(err) => assert_eq!(err.kind, clap::ErrorKind::ValueValidation),
+        Ok(_) => panic!("expected a minimum above the maximum to be rejected"),
+    }
+    assert!(parse_with("min-size = '20x30'", &["--max-size", "100x20"]).is_err());
+}